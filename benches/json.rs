@@ -0,0 +1,63 @@
+//! Measures json output of many documents and compares transcoding compact
+//! documents with validating and copying them. Run with `cargo bench`.
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    time::Instant,
+};
+
+const BINARY_PATH: &str = env!("CARGO_BIN_EXE_nuq");
+const DOCUMENTS: usize = 200_000;
+const ELEMENT: &str = r#"{"name":"a \"quoted\" name","tags":["x","y"],"size":12345,"ok":true}"#;
+
+fn main() {
+    let input = format!("[{}]", vec![ELEMENT; DOCUMENTS].join(","));
+    for (name, args) in [("compact", &[".[]"][..]), ("pretty", &["--pretty", ".[]"])] {
+        let start = Instant::now();
+        let mut handle = Command::new(BINARY_PATH)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to launch nuq process.");
+        let mut stdin = handle.stdin.take().unwrap();
+        stdin
+            .write_all(input.as_bytes())
+            .expect("Failed to write to nuq stdin.");
+        drop(stdin);
+        let output = handle
+            .wait_with_output()
+            .expect("Failed to await nuq process.");
+        assert!(output.status.success());
+        println!(
+            "{name} json output of {DOCUMENTS} documents: {:?}",
+            start.elapsed()
+        );
+    }
+    compare_compact_writes();
+}
+
+/// The write of compact json before and after skipping the transcoding.
+fn compare_compact_writes() {
+    let documents = vec![format!("{ELEMENT}\n"); DOCUMENTS];
+    let start = Instant::now();
+    let mut transcoded = Vec::<u8>::new();
+    for document in &documents {
+        let mut de = serde_json::Deserializer::from_str(document);
+        let mut se = serde_json::Serializer::new(&mut transcoded);
+        serde_transcode::transcode(&mut de, &mut se).expect("Failed to transcode.");
+        transcoded.push(b'\n');
+    }
+    println!("transcoding {DOCUMENTS} documents: {:?}", start.elapsed());
+    let start = Instant::now();
+    let mut copied = Vec::<u8>::new();
+    for document in &documents {
+        serde_json::from_str::<serde::de::IgnoredAny>(document).expect("Invalid json.");
+        copied.extend_from_slice(document.as_bytes());
+    }
+    println!(
+        "validating and copying {DOCUMENTS} documents: {:?}",
+        start.elapsed()
+    );
+    assert_eq!(transcoded, copied);
+}
@@ -0,0 +1,34 @@
+//! Measures raw output of a large json string. Run with `cargo bench`.
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    time::Instant,
+};
+
+const BINARY_PATH: &str = env!("CARGO_BIN_EXE_nuq");
+
+fn main() {
+    let text = r#"a \"quoted\" line\n"#.repeat(500_000);
+    let input = format!(r#"{{"text":"{text}"}}"#);
+    let start = Instant::now();
+    let mut handle = Command::new(BINARY_PATH)
+        .args(["-r", ".text"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to launch nuq process.");
+    let mut stdin = handle.stdin.take().unwrap();
+    stdin
+        .write_all(input.as_bytes())
+        .expect("Failed to write to nuq stdin.");
+    drop(stdin);
+    let output = handle
+        .wait_with_output()
+        .expect("Failed to await nuq process.");
+    assert!(output.status.success());
+    println!(
+        "raw output of a {} byte string: {:?}",
+        input.len(),
+        start.elapsed()
+    );
+}
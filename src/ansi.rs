@@ -0,0 +1,54 @@
+/// Removes ANSI escape sequences, e.g. colors of a terminal, from input.
+pub fn strip(input: &[u8]) -> Vec<u8> {
+    const ESC: u8 = 0x1b;
+    const BEL: u8 = 0x07;
+    let mut output = Vec::with_capacity(input.len());
+    let mut idx = 0;
+    while idx < input.len() {
+        if input[idx] != ESC {
+            output.push(input[idx]);
+            idx += 1;
+            continue;
+        }
+        idx += 1;
+        match input.get(idx) {
+            // control sequence, terminated by a byte in 0x40..=0x7e
+            Some(b'[') => {
+                idx += 1;
+                while idx < input.len() && !(0x40..=0x7e).contains(&input[idx]) {
+                    idx += 1;
+                }
+                idx += 1;
+            }
+            // operating system command, terminated by BEL or ESC \
+            Some(b']') => {
+                idx += 1;
+                while idx < input.len() {
+                    if input[idx] == BEL {
+                        idx += 1;
+                        break;
+                    }
+                    if input[idx] == ESC && input.get(idx + 1) == Some(&b'\\') {
+                        idx += 2;
+                        break;
+                    }
+                    idx += 1;
+                }
+            }
+            Some(_) => idx += 1,
+            None => {}
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::strip;
+
+    #[test]
+    fn colors() {
+        let colored = b"\x1b[38;2;191;97;106m{\"a\"\x1b[0m: 1}\x1b]0;title\x07";
+        assert_eq!(strip(colored), b"{\"a\": 1}");
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// Reads the path and content of every regular file in a tar archive.
+pub fn read_tar<R: Read>(reader: R) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut files = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        files.push((path, content));
+    }
+    Ok(files)
+}
+
+/// Opens a tar archive and reads all files within.
+pub fn read_tar_file(path: &Path) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let file = std::fs::File::open(path)
+        .map_err(|err| anyhow::anyhow!("failed to open {}: {}", path.display(), err))?;
+    read_tar(file)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{error::Error, path::PathBuf};
+
+    use clap::Parser;
+
+    use super::read_tar;
+    use crate::{Args, FileFormat, ReadOptions};
+
+    fn fixture() -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in [("a.json", r#"{"a":1}"#), ("dir/b.yaml", "b: 2\n")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content.as_bytes())?;
+        }
+        Ok(builder.into_inner()?)
+    }
+
+    #[test]
+    fn entries() -> Result<(), Box<dyn Error>> {
+        let files = read_tar(fixture()?.as_slice())?;
+        assert_eq!(
+            files,
+            vec![
+                (PathBuf::from("a.json"), br#"{"a":1}"#.to_vec()),
+                (PathBuf::from("dir/b.yaml"), b"b: 2\n".to_vec()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn inputs_by_extension() -> Result<(), Box<dyn Error>> {
+        let path = std::env::temp_dir().join("nuq-archive-inputs.tar");
+        std::fs::write(&path, fixture()?)?;
+        let args = Args::parse_from(["nuq", "--tar", &path.to_string_lossy(), "."]);
+        let mut inputs = args.make_inputs()?;
+        std::fs::remove_file(&path)?;
+        let formats = inputs
+            .iter_mut()
+            .map(|input| Ok(input.read_to_docs(&ReadOptions::default())?.input_format))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(formats, vec![FileFormat::Json, FileFormat::Yaml]);
+        Ok(())
+    }
+}
@@ -0,0 +1,501 @@
+use std::fmt::Write;
+
+use serde_json::{Map, Number, Value};
+
+/// The nesting depth of objects and arrays, beyond which a document is
+/// rejected instead of overflowing the stack. It matches `serde_json`.
+const MAX_DEPTH: usize = 128;
+
+/// Parses CoffeeScript Object Notation. Objects are either written in
+/// braces or as indented `key: value` lines, arrays always use brackets.
+/// Entries are separated by commas or line breaks and `#` starts a comment.
+/// String interpolation is not supported, so double quoted strings are
+/// read literally.
+pub fn parse(input: &str) -> anyhow::Result<Value> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_blank()?;
+    let value = if parser.at_key() {
+        let indent = parser.column();
+        parser.block(indent)?
+    } else {
+        parser.value()?
+    };
+    parser.skip_blank()?;
+    if parser.pos < parser.chars.len() {
+        anyhow::bail!(
+            "unexpected {:?} at {}",
+            parser.chars[parser.pos],
+            parser.location()
+        );
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(idx, char)| self.chars.get(self.pos + idx) == Some(&char))
+    }
+
+    fn location(&self) -> String {
+        let line = self.chars[..self.pos]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1;
+        format!("line {line} column {}", self.column() + 1)
+    }
+
+    fn nest(&mut self) -> anyhow::Result<()> {
+        if self.depth == MAX_DEPTH {
+            anyhow::bail!("nesting deeper than {} at {}", MAX_DEPTH, self.location());
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn column(&self) -> usize {
+        self.chars[..self.pos]
+            .iter()
+            .rev()
+            .take_while(|c| **c != '\n')
+            .count()
+    }
+
+    /// Skips spaces and a trailing comment, but not the line break.
+    fn skip_inline(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\r')) {
+            self.pos += 1;
+        }
+        if self.peek() == Some('#') && !self.starts_with("###") {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Skips whitespace, line breaks and all kinds of comments.
+    fn skip_blank(&mut self) -> anyhow::Result<()> {
+        loop {
+            self.skip_inline();
+            if self.starts_with("###") {
+                self.pos += 3;
+                while !self.starts_with("###") {
+                    if self.peek().is_none() {
+                        anyhow::bail!("unterminated block comment");
+                    }
+                    self.pos += 1;
+                }
+                self.pos += 3;
+            } else if self.peek() == Some('\n') {
+                self.pos += 1;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Whether a key followed by a colon starts at the position.
+    fn at_key(&mut self) -> bool {
+        let start = self.pos;
+        let found = self.key().is_ok() && {
+            self.skip_inline();
+            self.peek() == Some(':')
+        };
+        self.pos = start;
+        found
+    }
+
+    fn key(&mut self) -> anyhow::Result<String> {
+        match self.peek() {
+            Some('"' | '\'') => self.string(),
+            Some(char) if is_identifier(char) => {
+                let start = self.pos;
+                while self.peek().is_some_and(is_identifier) {
+                    self.pos += 1;
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+            _ => anyhow::bail!("expected a key at {}", self.location()),
+        }
+    }
+
+    /// Reads the `key: value` lines of an object indented by indent.
+    fn block(&mut self, indent: usize) -> anyhow::Result<Value> {
+        self.nest()?;
+        let mut map = Map::new();
+        loop {
+            self.skip_blank()?;
+            if self.peek().is_none() || self.column() < indent || !self.at_key() {
+                break;
+            }
+            if self.column() > indent {
+                anyhow::bail!("unexpected indentation at {}", self.location());
+            }
+            let (key, value) = self.entry(indent)?;
+            map.insert(key, value);
+            self.skip_inline();
+            if self.peek() == Some(',') {
+                self.pos += 1;
+            }
+        }
+        self.depth -= 1;
+        Ok(Value::Object(map))
+    }
+
+    fn entry(&mut self, indent: usize) -> anyhow::Result<(String, Value)> {
+        let key = self.key()?;
+        self.skip_inline();
+        if self.peek() != Some(':') {
+            anyhow::bail!("expected ':' after key {} at {}", key, self.location());
+        }
+        self.pos += 1;
+        self.skip_inline();
+        if !matches!(self.peek(), None | Some('\n')) {
+            return Ok((key, self.value()?));
+        }
+        // the value is on the following lines
+        self.skip_blank()?;
+        let nested = self.column();
+        if self.peek().is_none() || nested <= indent {
+            anyhow::bail!("missing value of key {} at {}", key, self.location());
+        }
+        if self.at_key() {
+            return Ok((key, self.block(nested)?));
+        }
+        Ok((key, self.value()?))
+    }
+
+    fn value(&mut self) -> anyhow::Result<Value> {
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"' | '\'') => Ok(Value::String(self.string()?)),
+            Some(_) => self.literal(),
+            None => anyhow::bail!("unexpected end of input"),
+        }
+    }
+
+    fn object(&mut self) -> anyhow::Result<Value> {
+        self.nest()?;
+        self.pos += 1;
+        let mut map = Map::new();
+        loop {
+            self.skip_separators()?;
+            if self.peek() == Some('}') {
+                self.pos += 1;
+                self.depth -= 1;
+                return Ok(Value::Object(map));
+            }
+            let indent = self.column();
+            let (key, value) = self.entry(indent)?;
+            map.insert(key, value);
+        }
+    }
+
+    fn array(&mut self) -> anyhow::Result<Value> {
+        self.nest()?;
+        self.pos += 1;
+        let mut array = Vec::new();
+        loop {
+            self.skip_separators()?;
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                self.depth -= 1;
+                return Ok(Value::Array(array));
+            }
+            if self.at_key() {
+                let indent = self.column();
+                array.push(self.block(indent)?);
+            } else {
+                array.push(self.value()?);
+            }
+        }
+    }
+
+    fn skip_separators(&mut self) -> anyhow::Result<()> {
+        loop {
+            self.skip_blank()?;
+            if self.peek() != Some(',') {
+                return Ok(());
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn literal(&mut self) -> anyhow::Result<Value> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|char| char.is_alphanumeric() || matches!(char, '-' | '+' | '.' | '_'))
+        {
+            self.pos += 1;
+        }
+        let literal: String = self.chars[start..self.pos].iter().collect();
+        Ok(match literal.as_str() {
+            "true" | "yes" | "on" => Value::Bool(true),
+            "false" | "no" | "off" => Value::Bool(false),
+            "null" => Value::Null,
+            _ => {
+                let Ok(number) = serde_json::from_str::<Number>(&literal) else {
+                    self.pos = start;
+                    anyhow::bail!("unexpected {:?} at {}", literal, self.location());
+                };
+                Value::Number(number)
+            }
+        })
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let quote = self.chars[self.pos];
+        let triple: String = [quote; 3].iter().collect();
+        if self.starts_with(&triple) {
+            self.pos += 3;
+            let start = self.pos;
+            while !self.starts_with(&triple) {
+                if self.peek().is_none() {
+                    anyhow::bail!("unterminated string");
+                }
+                self.pos += 1;
+            }
+            let text: String = self.chars[start..self.pos].iter().collect();
+            self.pos += 3;
+            return Ok(dedent(&text));
+        }
+        self.pos += 1;
+        let mut string = String::new();
+        loop {
+            let char = self
+                .peek()
+                .ok_or_else(|| anyhow::anyhow!("unterminated string"))?;
+            self.pos += 1;
+            match char {
+                '\\' => string.push(self.escape()?),
+                char if char == quote => return Ok(string),
+                char => string.push(char),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> anyhow::Result<char> {
+        let char = self
+            .peek()
+            .ok_or_else(|| anyhow::anyhow!("unterminated string"))?;
+        self.pos += 1;
+        Ok(match char {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            '0' => '\0',
+            'u' => {
+                let hex: String = self.chars.iter().skip(self.pos).take(4).collect();
+                self.pos += 4;
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| anyhow::anyhow!("invalid unicode escape \\u{}", hex))?
+            }
+            other => other,
+        })
+    }
+}
+
+fn is_identifier(char: char) -> bool {
+    char.is_alphanumeric() || matches!(char, '_' | '$')
+}
+
+/// Removes the leading line break and the common indentation of a triple
+/// quoted string.
+fn dedent(text: &str) -> String {
+    let text = text.strip_prefix('\n').unwrap_or(text);
+    let text = text.trim_end_matches([' ', '\t']);
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    let indent = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or_default();
+    text.lines()
+        .map(|line| line.get(indent..).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Writes a value as cson with nested objects as indented blocks.
+pub fn write(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => write_block(map, 0, &mut out),
+        _ => {
+            write_inline(value, 0, &mut out);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn write_block(map: &Map<String, Value>, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+    for (key, value) in map {
+        out.push_str(&pad);
+        if !key.is_empty() && key.chars().all(is_identifier) {
+            out.push_str(key);
+        } else {
+            out.push_str(&quote(key));
+        }
+        out.push(':');
+        match value {
+            Value::Object(child) if !child.is_empty() => {
+                out.push('\n');
+                write_block(child, indent + 2, out);
+            }
+            _ => {
+                out.push(' ');
+                write_inline(value, indent, out);
+                out.push('\n');
+            }
+        }
+    }
+}
+
+fn write_inline(value: &Value, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push_str("{\n");
+            write_block(map, indent + 2, out);
+            out.push_str(&pad);
+            out.push('}');
+        }
+        Value::Array(array) if !array.is_empty() => {
+            out.push_str("[\n");
+            for element in array {
+                out.push_str(&pad);
+                out.push_str("  ");
+                write_inline(element, indent + 2, out);
+                out.push('\n');
+            }
+            out.push_str(&pad);
+            out.push(']');
+        }
+        Value::Object(_) => out.push_str("{}"),
+        Value::Array(_) => out.push_str("[]"),
+        Value::String(string) => out.push_str(&quote(string)),
+        _ => out.push_str(&value.to_string()),
+    }
+}
+
+/// Single quotes avoid the interpolation of double quoted strings.
+fn quote(string: &str) -> String {
+    let mut quoted = String::from("'");
+    for char in string.chars() {
+        match char {
+            '\'' => quoted.push_str("\\'"),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            char if char.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", u32::from(char));
+            }
+            char => quoted.push(char),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use serde_json::json;
+
+    use super::{parse, write};
+
+    const FIXTURE: &str = r#"
+# editor settings
+editor:
+  fontSize: 14
+  'tab-length': 2
+  softWrap: yes
+###
+block comment
+###
+packages: [
+  'linter'
+  "minimap", 'tree-view'
+]
+keymap: {
+  'ctrl-s': 'save', ignored: null
+  nested:
+    deep: -1.5
+}
+description: '''
+  first line
+    second line
+'''
+"#;
+
+    #[test]
+    fn fixture() -> Result<(), Box<dyn Error>> {
+        assert_eq!(
+            parse(FIXTURE)?,
+            json!({
+                "editor": {"fontSize": 14, "tab-length": 2, "softWrap": true},
+                "packages": ["linter", "minimap", "tree-view"],
+                "keymap": {"ctrl-s": "save", "ignored": null, "nested": {"deep": -1.5}},
+                "description": "first line\n  second line",
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip() -> Result<(), Box<dyn Error>> {
+        let value = json!({
+            "a": {"b": [1, {"c": "it's"}, [], {}], "d": "#{x}\n"},
+            "e e": null,
+            "f": [[true]],
+        });
+        assert_eq!(parse(&write(&value))?, value);
+        assert_eq!(parse(&write(&json!([1, "a"])))?, json!([1, "a"]));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse("a: b").is_err());
+        assert!(parse("a: [1, 2").is_err());
+        assert!(parse("a = 1").is_err());
+        assert!(parse("{a 12}").is_err());
+        assert!(parse("[{a 1}]").is_err());
+    }
+
+    #[test]
+    fn depth() -> Result<(), Box<dyn Error>> {
+        let nested = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        assert!(parse(&nested)?.is_array());
+        assert!(parse(&"[".repeat(100_000)).is_err());
+        assert!(parse(&"{a:".repeat(100_000)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use serde_json::Value;
+
+/// Summarizes the structure of a document in a single line, e.g.
+/// `yaml object with 2 keys`.
+pub fn describe(value: &Value, format: &str) -> String {
+    let plural = |count: usize, noun: &str| {
+        if count == 1 {
+            format!("1 {noun}")
+        } else {
+            format!("{count} {noun}s")
+        }
+    };
+    match value {
+        Value::Object(map) => format!("{format} object with {}", plural(map.len(), "key")),
+        Value::Array(array) => format!("{format} array with {}", plural(array.len(), "element")),
+        Value::String(_) => format!("{format} string"),
+        Value::Number(_) => format!("{format} number"),
+        Value::Bool(_) => format!("{format} boolean"),
+        Value::Null => format!("{format} null"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::describe;
+
+    #[test]
+    fn object() {
+        assert_eq!(
+            describe(&json!({"a": 1, "b": [1, 2]}), "yaml"),
+            "yaml object with 2 keys"
+        );
+    }
+
+    #[test]
+    fn array() {
+        assert_eq!(describe(&json!([{}]), "json"), "json array with 1 element");
+        assert_eq!(describe(&json!([]), "toml"), "toml array with 0 elements");
+    }
+}
@@ -0,0 +1,84 @@
+use std::io::Write;
+
+/// Writes a unified diff between the rendered input and output. Colors
+/// additions green, deletions red and hunk headers cyan if requested.
+pub fn write_unified<W: Write>(
+    before: &str,
+    after: &str,
+    context: usize,
+    color: bool,
+    writer: &mut W,
+) -> anyhow::Result<()> {
+    let diff = similar::TextDiff::from_lines(before, after);
+    let unified = diff
+        .unified_diff()
+        .context_radius(context)
+        .header("input", "output")
+        .to_string();
+    for line in unified.split_inclusive('\n') {
+        let code = if line.starts_with("+++") || line.starts_with("---") {
+            "1"
+        } else if line.starts_with('+') {
+            "32"
+        } else if line.starts_with('-') {
+            "31"
+        } else if line.starts_with("@@") {
+            "36"
+        } else {
+            ""
+        };
+        if !color || code.is_empty() {
+            writer.write_all(line.as_bytes())?;
+            continue;
+        }
+        let text = line.trim_end_matches('\n');
+        write!(writer, "\x1b[{code}m{text}\x1b[0m")?;
+        if text.len() < line.len() {
+            writer.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use super::write_unified;
+
+    fn diff(color: bool) -> Result<String, Box<dyn Error>> {
+        let mut buf = Vec::<u8>::new();
+        write_unified("a: 1\nb: 2\n", "a: 1\nb: 3\n", 3, color, &mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn plain() -> Result<(), Box<dyn Error>> {
+        assert_eq!(
+            diff(false)?,
+            "--- input\n+++ output\n@@ -1,2 +1,2 @@\n a: 1\n-b: 2\n+b: 3\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn colored() -> Result<(), Box<dyn Error>> {
+        let colored = diff(true)?;
+        assert!(colored.contains("\x1b[31m-b: 2\x1b[0m\n"));
+        assert!(colored.contains("\x1b[32m+b: 3\x1b[0m\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn context_lines() -> Result<(), Box<dyn Error>> {
+        let before = "a\nb\nc\nd\ne\n";
+        let after = "a\nb\nC\nd\ne\n";
+        let mut buf = Vec::<u8>::new();
+        write_unified(before, after, 1, false, &mut buf)?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "--- input\n+++ output\n@@ -2,3 +2,3 @@\n b\n-c\n+C\n d\n"
+        );
+        Ok(())
+    }
+}
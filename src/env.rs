@@ -0,0 +1,198 @@
+use std::io::Write;
+
+use serde_json::{Map, Value};
+
+/// Parses a dotenv style file of KEY=VALUE lines into a json object.
+/// Blank lines and lines starting with # are skipped and a leading export
+/// keyword is stripped. Values can be single quoted (literal), double quoted
+/// (with \n, \t, \" and \\ escapes) or unquoted, in which case a trailing
+/// backslash continues the value on the next line. Any other shell syntax,
+/// like variable expansion or command substitution, is not supported.
+pub fn parse(input: &str) -> anyhow::Result<Value> {
+    let mut map = Map::new();
+    let mut lines = input.lines().enumerate();
+    while let Some((idx, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let (key, raw) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("line {} is not a KEY=VALUE pair", idx + 1))?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            anyhow::bail!("line {} has an invalid key: {:?}", idx + 1, key);
+        }
+        let raw = raw.trim();
+        let value = if let Some(quoted) = raw.strip_prefix('\'') {
+            quoted
+                .strip_suffix('\'')
+                .ok_or_else(|| anyhow::anyhow!("line {} has an unterminated quote", idx + 1))?
+                .to_owned()
+        } else if let Some(quoted) = raw.strip_prefix('"') {
+            let quoted = quoted
+                .strip_suffix('"')
+                .ok_or_else(|| anyhow::anyhow!("line {} has an unterminated quote", idx + 1))?;
+            unescape(quoted)
+        } else {
+            let mut value = raw.to_owned();
+            while let Some(continued) = value.strip_suffix('\\') {
+                value = continued.to_owned();
+                match lines.next() {
+                    Some((_, next)) => value.push_str(next.trim()),
+                    None => break,
+                }
+            }
+            value
+        };
+        map.insert(key.to_owned(), Value::String(value));
+    }
+    Ok(Value::Object(map))
+}
+
+fn unescape(quoted: &str) -> String {
+    let mut value = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            value.push(char);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some(other) => value.push(other),
+            None => value.push('\\'),
+        }
+    }
+    value
+}
+
+/// Writes a flat json object as KEY=VALUE lines, optionally prefixed
+/// with export and sorted by key. Values are quoted, so the output can
+/// be sourced by a shell. Keys, which are not valid shell variable names,
+/// and line breaks can not be written safely and are rejected.
+pub fn write<W: Write>(
+    value: &Value,
+    export: bool,
+    sort: bool,
+    writer: &mut W,
+) -> anyhow::Result<()> {
+    let Value::Object(map) = value else {
+        anyhow::bail!("env output requires an object");
+    };
+    let prefix = if export { "export " } else { "" };
+    let mut entries: Vec<_> = map.iter().collect();
+    if sort {
+        entries.sort_by_key(|(key, _)| *key);
+    }
+    for (key, value) in entries {
+        if !is_name(key) {
+            anyhow::bail!("env output requires variable names as keys, got {:?}", key);
+        }
+        let value = match value {
+            Value::String(string) => string.clone(),
+            Value::Null => String::new(),
+            Value::Bool(_) | Value::Number(_) => value.to_string(),
+            Value::Array(_) | Value::Object(_) => {
+                anyhow::bail!("env output does not support nested values for key {}", key)
+            }
+        };
+        if value.contains(['\n', '\r']) {
+            anyhow::bail!(
+                "env output does not support line breaks in the value of key {}",
+                key
+            );
+        }
+        writeln!(writer, "{prefix}{key}={}", quote(&value))?;
+    }
+    Ok(())
+}
+
+/// Whether the key matches `[A-Za-z_][A-Za-z0-9_]*`.
+fn is_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|char| char.is_ascii_alphabetic() || char == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+/// Leaves values consisting of shell safe characters unquoted. Everything
+/// else is single quoted or, when containing a single quote itself, double
+/// quoted with the characters special to the shell escaped.
+fn quote(value: &str) -> String {
+    let safe = |char: char| char.is_ascii_alphanumeric() || "_-.,:/@%+=".contains(char);
+    if value.chars().all(safe) {
+        return value.to_owned();
+    }
+    if !value.contains('\'') {
+        return format!("'{value}'");
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for char in value.chars() {
+        if matches!(char, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(char);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use serde_json::json;
+
+    use super::{parse, write};
+
+    #[test]
+    fn export_lines() -> Result<(), Box<dyn Error>> {
+        let input = "# comment\nexport A=1\nexport  B='x y'\nC=\"a\\nb\"\n\nD=long\\\n  value\n";
+        assert_eq!(
+            parse(input)?,
+            json!({"A": "1", "B": "x y", "C": "a\nb", "D": "longvalue"})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn write_export() -> Result<(), Box<dyn Error>> {
+        let mut buf = Vec::<u8>::new();
+        write(&json!({"B": "b", "A": 1}), true, true, &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, "export A=1\nexport B=b\n");
+        assert!(write(&json!({"A": [1]}), false, false, &mut Vec::<u8>::new()).is_err());
+        assert!(write(&json!({"A": "a\nb"}), false, false, &mut Vec::<u8>::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn write_invalid_key() -> Result<(), Box<dyn Error>> {
+        let mut buf = Vec::<u8>::new();
+        write(&json!({"_A1": 1}), true, false, &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, "export _A1=1\n");
+        for key in ["", "1A", "A-B", "A B", "$(id)", "A=B", "Ä"] {
+            let err = write(&json!({ key: 1 }), false, false, &mut Vec::<u8>::new()).unwrap_err();
+            assert!(err.to_string().contains("variable names"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_quoted() -> Result<(), Box<dyn Error>> {
+        let value = json!({"A": "x y", "B": "$HOME `id`", "C": "it's \"$x\"", "D": ""});
+        let mut buf = Vec::<u8>::new();
+        write(&value, false, false, &mut buf)?;
+        let output = String::from_utf8(buf)?;
+        assert_eq!(
+            output,
+            "A='x y'\nB='$HOME `id`'\nC=\"it's \\\"\\$x\\\"\"\nD=\n"
+        );
+        assert_eq!(parse(&output)?, value);
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+/// How array indices appear in flattened keys.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ArrayStyle {
+    /// Indices are joined like object keys, e.g. a.0.b
+    #[default]
+    Dot,
+    /// Indices are appended in brackets, e.g. a[0].b
+    Bracket,
+}
+
+/// The way flattened keys are built and split again.
+#[derive(Clone, Debug)]
+pub struct Style {
+    pub separator: String,
+    pub arrays: ArrayStyle,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            separator: ".".to_owned(),
+            arrays: ArrayStyle::default(),
+        }
+    }
+}
+
+/// Flattens nested objects and arrays into a single object, whose keys
+/// are the paths of the leaves joined by the separator, e.g. `a.b.0`.
+/// Empty objects and arrays are kept as leaves. Scalars are returned as is.
+pub fn flatten(value: &Value, style: &Style) -> Value {
+    match value {
+        Value::Object(_) | Value::Array(_) => Value::Object(flatten_to_map(value, style)),
+        _ => value.clone(),
+    }
+}
+
+/// Flattens an object or array into a map of leaf paths to values.
+pub fn flatten_to_map(value: &Value, style: &Style) -> Map<String, Value> {
+    let mut flat = Map::new();
+    flatten_into(value, None, style, &mut flat);
+    flat
+}
+
+fn flatten_into(value: &Value, path: Option<&str>, style: &Style, flat: &mut Map<String, Value>) {
+    let join = |key: &str| match path {
+        Some(path) => format!("{path}{}{key}", style.separator),
+        None => key.to_owned(),
+    };
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                flatten_into(child, Some(&join(key)), style, flat);
+            }
+        }
+        Value::Array(array) if !array.is_empty() => {
+            for (idx, child) in array.iter().enumerate() {
+                let key = match style.arrays {
+                    ArrayStyle::Dot => join(&idx.to_string()),
+                    ArrayStyle::Bracket => format!("{}[{idx}]", path.unwrap_or_default()),
+                };
+                flatten_into(child, Some(&key), style, flat);
+            }
+        }
+        _ => {
+            flat.insert(path.unwrap_or_default().to_owned(), value.clone());
+        }
+    }
+}
+
+/// A step of a flattened key into a nested value.
+enum Step<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Rebuilds nested objects and arrays from an object with flattened keys.
+/// With the dot style every numeric key becomes an array index. Indices
+/// are below the number of keys, as every array element has at least one,
+/// so larger numbers are kept as object keys. Values, which are not
+/// objects, are returned as is.
+pub fn unflatten(value: &Value, style: &Style) -> Value {
+    let Value::Object(flat) = value else {
+        return value.clone();
+    };
+    let mut nested = Value::Null;
+    for (key, leaf) in flat {
+        let mut target = &mut nested;
+        for step in steps(key, style, flat.len()) {
+            target = match step {
+                Step::Key(key) => {
+                    if !target.is_object() {
+                        *target = Value::Object(Map::new());
+                    }
+                    let Value::Object(map) = target else {
+                        unreachable!()
+                    };
+                    map.entry(key).or_insert(Value::Null)
+                }
+                Step::Index(idx) => {
+                    if !target.is_array() {
+                        *target = Value::Array(Vec::new());
+                    }
+                    let Value::Array(array) = target else {
+                        unreachable!()
+                    };
+                    if array.len() <= idx {
+                        array.resize(idx + 1, Value::Null);
+                    }
+                    &mut array[idx]
+                }
+            };
+        }
+        *target = leaf.clone();
+    }
+    nested
+}
+
+fn steps<'a>(key: &'a str, style: &Style, limit: usize) -> Vec<Step<'a>> {
+    if key.is_empty() {
+        return Vec::new();
+    }
+    let mut steps = Vec::new();
+    for segment in key.split(style.separator.as_str()) {
+        match style.arrays {
+            ArrayStyle::Dot => match segment.parse() {
+                Ok(idx) if idx < limit => steps.push(Step::Index(idx)),
+                _ => steps.push(Step::Key(segment)),
+            },
+            ArrayStyle::Bracket => {
+                let (name, mut indices) = segment.split_once('[').unwrap_or((segment, ""));
+                if !name.is_empty() || indices.is_empty() {
+                    steps.push(Step::Key(name));
+                }
+                while let Some((idx, rest)) = indices.split_once(']') {
+                    match idx.parse() {
+                        Ok(idx) if idx < limit => steps.push(Step::Index(idx)),
+                        // not an index, so the brackets are part of the key
+                        _ => return vec![Step::Key(key)],
+                    }
+                    indices = rest.strip_prefix('[').unwrap_or(rest);
+                }
+            }
+        }
+    }
+    steps
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{flatten, unflatten, ArrayStyle, Style};
+
+    #[test]
+    fn nested() {
+        let value = json!({"a": {"b": 1, "c": [true, {"d": null}]}, "e": {}, "f": "g"});
+        let style = Style::default();
+        let flat = flatten(&value, &style);
+        assert_eq!(
+            flat,
+            json!({"a.b": 1, "a.c.0": true, "a.c.1.d": null, "e": {}, "f": "g"})
+        );
+        assert_eq!(unflatten(&flat, &style), value);
+    }
+
+    #[test]
+    fn scalar() {
+        assert_eq!(flatten(&json!("a"), &Style::default()), json!("a"));
+        assert_eq!(unflatten(&json!("a"), &Style::default()), json!("a"));
+    }
+
+    #[test]
+    fn custom_style() {
+        let value = json!({"a.b": {"c": [1, [2, {"d": 3}]]}, "e": [4]});
+        let style = Style {
+            separator: "/".to_owned(),
+            arrays: ArrayStyle::Bracket,
+        };
+        let flat = flatten(&value, &style);
+        assert_eq!(
+            flat,
+            json!({"a.b/c[0]": 1, "a.b/c[1][0]": 2, "a.b/c[1][1]/d": 3, "e[0]": 4})
+        );
+        assert_eq!(unflatten(&flat, &style), value);
+        let root = json!([{"a": 1}]);
+        assert_eq!(flatten(&root, &style), json!({"[0]/a": 1}));
+        assert_eq!(unflatten(&flatten(&root, &style), &style), root);
+    }
+
+    #[test]
+    fn large_index() {
+        let style = Style::default();
+        for key in ["a.18446744073709551615", "a.5000000000000"] {
+            let flat = json!({ key: 1 });
+            let (_, idx) = key.split_once('.').unwrap();
+            assert_eq!(unflatten(&flat, &style), json!({"a": {idx: 1}}));
+        }
+        let style = Style {
+            separator: ".".to_owned(),
+            arrays: ArrayStyle::Bracket,
+        };
+        let flat = json!({"a[5000000000000]": 1});
+        assert_eq!(unflatten(&flat, &style), flat);
+        assert_eq!(
+            unflatten(&json!({"a.1": 1}), &Style::default()),
+            json!({"a": {"1": 1}})
+        );
+    }
+}
@@ -1,14 +1,109 @@
-use std::io::Write;
+use std::{fmt::Write as _, io::Write, path::Path};
 
+use clap::ValueEnum;
 use syntect::{
     easy::HighlightLines,
-    highlighting::{Style, ThemeSet},
+    highlighting::{Color, Style, Theme, ThemeSet},
     parsing::SyntaxSet,
     util::{as_24_bit_terminal_escaped, LinesWithEndings},
 };
 
 use crate::FileFormat;
 
+/// Theme used when none is given.
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// Number of colors the terminal supports. Colors of the theme are
+/// approximated by the closest available color.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorDepth {
+    #[default]
+    Truecolor,
+    #[value(name = "256")]
+    Ansi256,
+    #[value(name = "16")]
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Escapes the highlighted ranges with foreground colors of this depth.
+    fn escape(self, ranges: &[(Style, &str)]) -> String {
+        if self == ColorDepth::Truecolor {
+            return as_24_bit_terminal_escaped(ranges, false);
+        }
+        let mut escaped = String::new();
+        for (style, text) in ranges {
+            let code = match self {
+                ColorDepth::Ansi256 => format!("38;5;{}", ansi256(style.foreground)),
+                _ => ansi16(style.foreground).to_string(),
+            };
+            let _ = write!(escaped, "\x1b[{code}m{text}");
+        }
+        escaped
+    }
+}
+
+fn distance(color: Color, rgb: (u8, u8, u8)) -> u32 {
+    let channel = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    channel(color.r, rgb.0) + channel(color.g, rgb.1) + channel(color.b, rgb.2)
+}
+
+/// Closest color of the xterm 256 color palette, which is either in the
+/// 6x6x6 color cube or on the grayscale ramp.
+fn ansi256(color: Color) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |value: u8| {
+        (0..LEVELS.len())
+            .min_by_key(|idx| LEVELS[*idx].abs_diff(value))
+            .unwrap_or_default()
+    };
+    let (r, g, b) = (level(color.r), level(color.g), level(color.b));
+    let cube = (LEVELS[r], LEVELS[g], LEVELS[b]);
+    let average = (u16::from(color.r) + u16::from(color.g) + u16::from(color.b)) / 3;
+    let gray_idx = u8::try_from(average.saturating_sub(3) / 10)
+        .unwrap_or(23)
+        .min(23);
+    let gray = 8 + 10 * gray_idx;
+    let cube_code = u8::try_from(16 + 36 * r + 6 * g + b).unwrap_or_default();
+    if distance(color, (gray, gray, gray)) < distance(color, cube) {
+        232 + gray_idx
+    } else {
+        cube_code
+    }
+}
+
+/// SGR code of the closest of the 16 basic terminal colors.
+fn ansi16(color: Color) -> u8 {
+    const PALETTE: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    let idx = (0..PALETTE.len())
+        .min_by_key(|idx| distance(color, PALETTE[*idx]))
+        .and_then(|idx| u8::try_from(idx).ok())
+        .unwrap_or_default();
+    // the bright colors have their own codes
+    if idx < 8 {
+        30 + idx
+    } else {
+        90 + idx - 8
+    }
+}
+
 pub struct Styles {
     syntax_set: SyntaxSet,
     theme_set: ThemeSet,
@@ -27,18 +122,60 @@ pub struct Writer<'a, W: Write> {
     buf: Vec<u8>,
     format: FileFormat,
     styles: &'a Styles,
+    theme: &'a Theme,
+    depth: ColorDepth,
     wrapped: W,
 }
 
 impl<'a, W: Write> Writer<'a, W> {
-    pub fn new(wrapped: W, format: FileFormat, styles: &'a Styles) -> Self {
+    /// Creates a writer highlighting with the named theme. Fails with the
+    /// available theme names, if the theme does not exist.
+    pub fn new(
+        wrapped: W,
+        format: FileFormat,
+        styles: &'a Styles,
+        theme: &str,
+    ) -> anyhow::Result<Self> {
+        let Some(found) = styles.theme_set.themes.get(theme) else {
+            let names: Vec<&str> = styles.theme_set.themes.keys().map(String::as_str).collect();
+            anyhow::bail!(
+                "unknown theme {}, available themes: {}",
+                theme,
+                names.join(", ")
+            );
+        };
+        Ok(Self::with_theme(wrapped, format, styles, found))
+    }
+
+    /// Creates a writer highlighting with a theme, which is not part of
+    /// the default themes.
+    pub fn with_theme(
+        wrapped: W,
+        format: FileFormat,
+        styles: &'a Styles,
+        theme: &'a Theme,
+    ) -> Self {
         Writer::<'a, W> {
             buf: Vec::default(),
             format,
             styles,
+            theme,
+            depth: ColorDepth::default(),
             wrapped,
         }
     }
+
+    #[must_use]
+    pub fn with_depth(mut self, depth: ColorDepth) -> Self {
+        self.depth = depth;
+        self
+    }
+}
+
+/// Loads a theme from a .tmTheme file.
+pub fn load_theme(path: &Path) -> anyhow::Result<Theme> {
+    ThemeSet::get_theme(path)
+        .map_err(|err| anyhow::anyhow!("failed to load theme {}: {}", path.display(), err))
 }
 
 impl<W: Write> Write for Writer<'_, W> {
@@ -48,34 +185,139 @@ impl<W: Write> Write for Writer<'_, W> {
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        self.write_highlight(self.format).map_err(|_| {
-            std::io::Error::new(std::io::ErrorKind::Other, "failed to highlight output")
-        })?;
+        self.write_highlight(self.format)
+            .map_err(|_| std::io::Error::other("failed to highlight output"))?;
         self.wrapped.flush()
     }
 }
 
 impl<W: Write> Writer<'_, W> {
     pub fn write_highlight(&mut self, format: FileFormat) -> anyhow::Result<()> {
+        // the buffer is written once, so flushing again does not repeat it
+        let buf = std::mem::take(&mut self.buf);
         let extension = format.to_extension();
-        let syntax = self.styles.syntax_set.find_syntax_by_extension(extension);
-        if syntax.is_none() {
-            self.wrapped.write_all(&self.buf)?;
+        let Some(syntax) = self.styles.syntax_set.find_syntax_by_extension(extension) else {
+            self.wrapped.write_all(&buf)?;
             return Ok(());
-        }
-        let syntax = syntax.unwrap();
-        let mut lighter =
-            HighlightLines::new(syntax, &self.styles.theme_set.themes["base16-ocean.dark"]);
-        let text = std::str::from_utf8(&self.buf)?;
+        };
+        let mut lighter = HighlightLines::new(syntax, self.theme);
+        let text = std::str::from_utf8(&buf)?;
         for line in LinesWithEndings::from(text) {
             let ranges: Vec<(Style, &str)> = lighter
                 .highlight_line(line, &self.styles.syntax_set)
                 .unwrap();
-            let mut escaped = as_24_bit_terminal_escaped(&ranges[..], false);
-            // reset colors
+            let mut escaped = self.depth.escape(&ranges);
+            let ending = if escaped.ends_with("\r\n") {
+                "\r\n"
+            } else if escaped.ends_with('\n') {
+                "\n"
+            } else {
+                ""
+            };
+            escaped.truncate(escaped.len() - ending.len());
+            // reset colors before the line break, so the next line and the
+            // prompt after the last line start uncolored
             escaped.push_str("\x1b[0m");
+            escaped.push_str(ending);
             self.wrapped.write_all(escaped.as_bytes())?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{error::Error, io::Write, path::Path};
+
+    use syntect::highlighting::Color;
+
+    use super::{ansi16, ansi256, load_theme, ColorDepth, Styles, Writer, DEFAULT_THEME};
+    use crate::FileFormat;
+
+    #[test]
+    fn unknown_theme() {
+        let styles = Styles::default();
+        let Err(err) = Writer::new(Vec::new(), FileFormat::Json, &styles, "bogus") else {
+            panic!("bogus theme was accepted");
+        };
+        let message = err.to_string();
+        assert!(message.contains("unknown theme bogus"));
+        assert!(message.contains(DEFAULT_THEME));
+        assert!(Writer::new(Vec::new(), FileFormat::Json, &styles, DEFAULT_THEME).is_ok());
+    }
+
+    #[test]
+    fn theme_file() -> Result<(), Box<dyn Error>> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/minimal.tmTheme");
+        let theme = load_theme(&path)?;
+        let styles = Styles::default();
+        let mut buf = Vec::<u8>::new();
+        let mut writer = Writer::with_theme(&mut buf, FileFormat::Json, &styles, &theme);
+        writer.write_all(br#"{"a":"b"}"#)?;
+        writer.flush()?;
+        let highlighted = String::from_utf8(buf)?;
+        // strings are green in the theme
+        assert!(highlighted.contains("\x1b[38;2;0;255;0m"));
+        assert!(load_theme(Path::new("missing.tmTheme")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn line_endings() -> Result<(), Box<dyn Error>> {
+        let styles = Styles::default();
+        let highlight = |format, text: &str| -> Result<String, Box<dyn Error>> {
+            let mut buf = Vec::<u8>::new();
+            let mut writer = Writer::new(&mut buf, format, &styles, DEFAULT_THEME)?;
+            writer.write_all(text.as_bytes())?;
+            writer.flush()?;
+            writer.flush()?;
+            drop(writer);
+            Ok(String::from_utf8(buf)?)
+        };
+        let json = highlight(FileFormat::Json, "{\n  \"a\": 1\n}\n")?;
+        assert_eq!(json.matches('\n').count(), 3);
+        assert_eq!(json.matches("\x1b[0m").count(), 3);
+        assert!(json.ends_with("\x1b[0m\n"));
+        assert!(json.lines().all(|line| line.ends_with("\x1b[0m")));
+        // ron has no syntax definition and is written as is
+        let ron = "(\n    a: [\n        1,\n    ],\n)\n";
+        assert_eq!(highlight(FileFormat::Ron, ron)?, ron);
+        Ok(())
+    }
+
+    #[test]
+    fn color_depth() -> Result<(), Box<dyn Error>> {
+        let styles = Styles::default();
+        let highlight = |depth| -> Result<String, Box<dyn Error>> {
+            let mut buf = Vec::<u8>::new();
+            let mut writer =
+                Writer::new(&mut buf, FileFormat::Json, &styles, DEFAULT_THEME)?.with_depth(depth);
+            writer.write_all(br#"{"a":1}"#)?;
+            writer.flush()?;
+            drop(writer);
+            Ok(String::from_utf8(buf)?)
+        };
+        let truecolor = highlight(ColorDepth::Truecolor)?;
+        assert!(truecolor.contains("\x1b[38;2;"));
+        let palette = highlight(ColorDepth::Ansi256)?;
+        assert!(palette.contains("\x1b[38;5;"));
+        assert!(!palette.contains("38;2;"));
+        let basic = highlight(ColorDepth::Ansi16)?;
+        assert!(!basic.contains("38;"));
+        let red = Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        let gray = Color {
+            r: 128,
+            g: 128,
+            b: 128,
+            a: 255,
+        };
+        assert_eq!((ansi256(red), ansi256(gray)), (196, 244));
+        assert_eq!((ansi16(red), ansi16(gray)), (91, 90));
+        Ok(())
+    }
+}
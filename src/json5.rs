@@ -0,0 +1,314 @@
+use serde_json::{Map, Number, Value};
+
+/// The nesting depth of objects and arrays, beyond which a document is
+/// rejected instead of overflowing the stack. It matches `serde_json`.
+const MAX_DEPTH: usize = 128;
+
+/// Parses a JSON5 document. Besides json it allows comments, trailing
+/// commas, identifier keys, single quoted strings, hexadecimal numbers and
+/// numbers with a leading plus or a leading or trailing decimal point.
+/// Infinity and NaN are rejected, as json can not represent them.
+pub fn parse(input: &str) -> anyhow::Result<Value> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_blank()?;
+    let value = parser.value()?;
+    parser.skip_blank()?;
+    if let Some(char) = parser.peek() {
+        anyhow::bail!("unexpected {:?} at {}", char, parser.pos);
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> anyhow::Result<char> {
+        let char = self
+            .peek()
+            .ok_or_else(|| anyhow::anyhow!("unexpected end of input"))?;
+        self.pos += 1;
+        Ok(char)
+    }
+
+    fn expect(&mut self, expected: char) -> anyhow::Result<()> {
+        match self.next()? {
+            char if char == expected => Ok(()),
+            char => anyhow::bail!(
+                "expected {:?}, got {:?} at {}",
+                expected,
+                char,
+                self.pos - 1
+            ),
+        }
+    }
+
+    fn skip_blank(&mut self) -> anyhow::Result<()> {
+        loop {
+            match (self.peek(), self.chars.get(self.pos + 1)) {
+                (Some(char), _) if char.is_whitespace() || char == '\u{feff}' => self.pos += 1,
+                (Some('/'), Some('/')) => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.pos += 1;
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    self.pos += 2;
+                    while !(self.peek() == Some('*') && self.chars.get(self.pos + 1) == Some(&'/'))
+                    {
+                        self.next()
+                            .map_err(|_| anyhow::anyhow!("unterminated comment"))?;
+                    }
+                    self.pos += 2;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn value(&mut self) -> anyhow::Result<Value> {
+        match self.peek() {
+            Some(char @ ('{' | '[')) => {
+                if self.depth == MAX_DEPTH {
+                    anyhow::bail!("nesting deeper than {} at {}", MAX_DEPTH, self.pos);
+                }
+                self.depth += 1;
+                let value = if char == '{' {
+                    self.object()
+                } else {
+                    self.array()
+                };
+                self.depth -= 1;
+                value
+            }
+            Some('"' | '\'') => Ok(Value::String(self.string()?)),
+            Some(_) => self.literal(),
+            None => anyhow::bail!("unexpected end of input"),
+        }
+    }
+
+    fn object(&mut self) -> anyhow::Result<Value> {
+        self.pos += 1;
+        let mut map = Map::new();
+        loop {
+            self.skip_blank()?;
+            if self.peek() == Some('}') {
+                self.pos += 1;
+                return Ok(Value::Object(map));
+            }
+            let key = match self.peek() {
+                Some('"' | '\'') => self.string()?,
+                _ => self.identifier()?,
+            };
+            self.skip_blank()?;
+            self.expect(':')?;
+            self.skip_blank()?;
+            map.insert(key, self.value()?);
+            self.skip_blank()?;
+            match self.next()? {
+                ',' => {}
+                '}' => return Ok(Value::Object(map)),
+                char => anyhow::bail!("expected ',' or '}}', got {:?} at {}", char, self.pos - 1),
+            }
+        }
+    }
+
+    fn array(&mut self) -> anyhow::Result<Value> {
+        self.pos += 1;
+        let mut array = Vec::new();
+        loop {
+            self.skip_blank()?;
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::Array(array));
+            }
+            array.push(self.value()?);
+            self.skip_blank()?;
+            match self.next()? {
+                ',' => {}
+                ']' => return Ok(Value::Array(array)),
+                char => anyhow::bail!("expected ',' or ']', got {:?} at {}", char, self.pos - 1),
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> anyhow::Result<String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|char| char.is_alphanumeric() || matches!(char, '_' | '$'))
+        {
+            self.pos += 1;
+        }
+        if self.pos == start || self.chars[start].is_ascii_digit() {
+            anyhow::bail!("expected a key at {}", start);
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn literal(&mut self) -> anyhow::Result<Value> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|char| char.is_alphanumeric() || matches!(char, '-' | '+' | '.'))
+        {
+            self.pos += 1;
+        }
+        let literal: String = self.chars[start..self.pos].iter().collect();
+        match literal.as_str() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            "null" => return Ok(Value::Null),
+            _ => {}
+        }
+        number(&literal).ok_or_else(|| anyhow::anyhow!("unexpected {:?} at {}", literal, start))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let quote = self.next()?;
+        let mut string = String::new();
+        loop {
+            match self.next()? {
+                '\\' => match self.next()? {
+                    'n' => string.push('\n'),
+                    't' => string.push('\t'),
+                    'r' => string.push('\r'),
+                    'b' => string.push('\u{8}'),
+                    'f' => string.push('\u{c}'),
+                    'v' => string.push('\u{b}'),
+                    '0' => string.push('\0'),
+                    // escaped line breaks continue the string on the next line
+                    '\n' => {}
+                    '\r' => {
+                        if self.peek() == Some('\n') {
+                            self.pos += 1;
+                        }
+                    }
+                    'x' => string.push(self.hex_char(2)?),
+                    'u' => string.push(self.unicode()?),
+                    other => string.push(other),
+                },
+                '\n' => anyhow::bail!("unescaped line break in string"),
+                char if char == quote => return Ok(string),
+                char => string.push(char),
+            }
+        }
+    }
+
+    fn hex(&mut self, len: usize) -> anyhow::Result<u32> {
+        let hex: String = self.chars.iter().skip(self.pos).take(len).collect();
+        self.pos += len;
+        u32::from_str_radix(&hex, 16).map_err(|_| anyhow::anyhow!("invalid escape {}", hex))
+    }
+
+    fn hex_char(&mut self, len: usize) -> anyhow::Result<char> {
+        let code = self.hex(len)?;
+        char::from_u32(code).ok_or_else(|| anyhow::anyhow!("invalid character {:x}", code))
+    }
+
+    /// Reads a \u escape, which may be the first half of a surrogate pair.
+    fn unicode(&mut self) -> anyhow::Result<char> {
+        let high = self.hex(4)?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high)
+                .ok_or_else(|| anyhow::anyhow!("invalid escape {:x}", high));
+        }
+        if self.next()? != '\\' || self.next()? != 'u' {
+            anyhow::bail!("unpaired surrogate {:x}", high);
+        }
+        let low = self.hex(4)?;
+        if !(0xdc00..0xe000).contains(&low) {
+            anyhow::bail!("unpaired surrogate {:x}", high);
+        }
+        let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+        char::from_u32(code).ok_or_else(|| anyhow::anyhow!("invalid surrogate pair"))
+    }
+}
+
+fn number(literal: &str) -> Option<Value> {
+    let (negative, digits) = match literal.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, literal.strip_prefix('+').unwrap_or(literal)),
+    };
+    if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        let value = i64::from_str_radix(hex, 16).ok()?;
+        return Some(Value::from(if negative { -value } else { value }));
+    }
+    // json requires digits around the decimal point
+    let mut normalized = String::from(if negative { "-" } else { "" });
+    if digits.starts_with('.') {
+        normalized.push('0');
+    }
+    normalized.push_str(digits);
+    if normalized.ends_with('.') {
+        normalized.push('0');
+    }
+    let normalized = normalized.replace(".e", ".0e").replace(".E", ".0E");
+    serde_json::from_str::<Number>(&normalized)
+        .ok()
+        .map(Value::Number)
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use serde_json::json;
+
+    use super::parse;
+
+    #[test]
+    fn comments_and_trailing_commas() -> Result<(), Box<dyn Error>> {
+        let json5 = r"{
+            // line comment
+            name: 'nuq', /* block comment */
+            'quoted key': 'it\'s',
+            numbers: [0x1F, +1, .5, 2., -3,],
+        }";
+        assert_eq!(
+            parse(json5)?,
+            json!({"name": "nuq", "quoted key": "it's", "numbers": [31, 1, 0.5, 2.0, -3]})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse("{a: Infinity}").is_err());
+        assert!(parse("{a: 1").is_err());
+        assert!(parse("a: 1").is_err());
+        assert!(parse("[1] [2]").is_err());
+    }
+
+    #[test]
+    fn surrogates() -> Result<(), Box<dyn Error>> {
+        assert_eq!(parse(r"'\ud83d\ude00'")?, json!("\u{1f600}"));
+        assert!(parse(r"'\ud800\u0041'").is_err());
+        assert!(parse(r"'\ud800\ud800'").is_err());
+        assert!(parse(r"'\ud800'").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn depth() -> Result<(), Box<dyn Error>> {
+        let nested = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        assert!(parse(&nested)?.is_array());
+        assert!(parse(&"[".repeat(100_000)).is_err());
+        assert!(parse(&"{a:".repeat(100_000)).is_err());
+        Ok(())
+    }
+}
@@ -1,14 +1,44 @@
+use base64::Engine;
 use clap::{Parser, ValueEnum};
 use std::{
     fs::File,
-    io::{Cursor, IsTerminal, Read, Write},
+    io::{BufReader, Cursor, IsTerminal, Read, Write},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
+mod ansi;
+#[cfg(feature = "tar")]
+mod archive;
+mod cson;
+mod describe;
+mod diff;
+mod env;
+mod flatten;
 mod highlight;
+mod json5;
+mod merge;
+mod message_pack;
+mod modeline;
+mod paths;
+mod property_list;
+mod sample;
+mod sort;
+mod strict;
+mod tabular;
+mod toml_style;
+mod worker;
+mod xml;
+mod yaml;
 
 fn ext_from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<String> {
     let path = path.as_ref();
+    // dotfiles like .env have no extension, so use their name instead
+    if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+        if let Some(ext) = name.strip_prefix('.').filter(|ext| !ext.contains('.')) {
+            return Ok(ext.to_owned());
+        }
+    }
     let os_ext = path
         .extension()
         .ok_or_else(|| anyhow::anyhow!("input path {} has no extension", path.display()))?;
@@ -20,19 +50,55 @@ fn ext_from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<String> {
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum FileFormat {
+    #[value(alias = "j")]
     Json,
+    #[value(alias = "y")]
     Yaml,
+    #[value(alias = "r")]
     Ron,
+    #[value(alias = "t")]
     Toml,
+    Plist,
+    Env,
+    Csv,
+    Tsv,
+    Xml,
+    Cson,
+    Json5,
+    #[value(name = "msgpack")]
+    MessagePack,
+    Raw,
+}
+
+/// Parses the formats, which can be read. Raw is an output format only,
+/// so it is neither accepted nor listed.
+fn readable_format() -> impl clap::builder::TypedValueParser<Value = FileFormat> {
+    use clap::builder::TypedValueParser;
+    let formats = FileFormat::value_variants()
+        .iter()
+        .filter(|format| **format != FileFormat::Raw)
+        .filter_map(ValueEnum::to_possible_value);
+    clap::builder::PossibleValuesParser::new(formats)
+        .map(|name| FileFormat::from_str(&name, false).expect("possible values are file formats"))
 }
 
 impl FileFormat {
+    /// Resolves the format of a file extension. This is a plain lookup,
+    /// so no caching is required for many inputs with the same extension.
     fn from_extension(ext: &str) -> anyhow::Result<FileFormat> {
         match ext {
             "json" | "jsonl" => Ok(FileFormat::Json),
             "ron" => Ok(FileFormat::Ron),
             "yaml" | "yml" => Ok(FileFormat::Yaml),
             "toml" => Ok(FileFormat::Toml),
+            "plist" => Ok(FileFormat::Plist),
+            "env" => Ok(FileFormat::Env),
+            "csv" => Ok(FileFormat::Csv),
+            "tsv" => Ok(FileFormat::Tsv),
+            "xml" => Ok(FileFormat::Xml),
+            "cson" => Ok(FileFormat::Cson),
+            "json5" => Ok(FileFormat::Json5),
+            "msgpack" | "mpk" => Ok(FileFormat::MessagePack),
             _ => Err(anyhow::anyhow!("unknown extension: {}", ext)),
         }
     }
@@ -44,16 +110,44 @@ impl FileFormat {
             FileFormat::Yaml => "yaml",
             FileFormat::Ron => "ron",
             FileFormat::Toml => "toml",
+            FileFormat::Plist => "plist",
+            FileFormat::Env => "env",
+            FileFormat::Csv => "csv",
+            FileFormat::Tsv => "tsv",
+            FileFormat::Xml => "xml",
+            FileFormat::Cson => "cson",
+            FileFormat::Json5 => "json5",
+            FileFormat::MessagePack => "msgpack",
+            FileFormat::Raw => "raw",
+        }
+    }
+
+    /// Field delimiter of the tabular formats.
+    fn delimiter(self) -> u8 {
+        match self {
+            FileFormat::Tsv => b'\t',
+            _ => b',',
         }
     }
 
-    fn read_to_json<R: Read>(self, mut reader: R) -> anyhow::Result<Vec<String>> {
+    fn read_to_json<R: Read>(
+        self,
+        mut reader: R,
+        options: &ReadOptions,
+    ) -> anyhow::Result<Vec<String>> {
         let mut json = Vec::<u8>::new();
+        let limit = options.input_limit.unwrap_or(usize::MAX);
         match self {
             FileFormat::Json => {
                 let de = serde_json::Deserializer::from_reader(reader);
                 let mut docs = Vec::<String>::new();
-                for doc in de.into_iter::<serde_json::Value>() {
+                if options.strict_json {
+                    for doc in de.into_iter::<strict::StrictValue>().take(limit) {
+                        docs.push(doc?.0.to_string());
+                    }
+                    return anyhow::Ok(docs);
+                }
+                for doc in de.into_iter::<serde_json::Value>().take(limit) {
                     docs.push(doc?.to_string());
                 }
                 return anyhow::Ok(docs);
@@ -62,7 +156,7 @@ impl FileFormat {
                 let de = serde_yaml::Deserializer::from_reader(reader);
                 let mut docs = Vec::<String>::new();
                 // deserializer implements iterator for multi document yamls
-                for doc in de {
+                for doc in de.take(limit) {
                     let mut buf = Vec::<u8>::new();
                     let mut se = serde_json::Serializer::new(Cursor::new(&mut buf));
                     serde_transcode::transcode(doc, &mut se)?;
@@ -85,88 +179,351 @@ impl FileFormat {
                 let mut se = serde_json::Serializer::new(Cursor::new(&mut json));
                 serde_transcode::transcode(de, &mut se)?;
             }
+            FileFormat::Plist => {
+                // binary plists require seeking
+                let mut input = Vec::<u8>::new();
+                reader.read_to_end(&mut input)?;
+                let value = property_list::read_to_json(Cursor::new(input))?;
+                return anyhow::Ok(vec![value.to_string()]);
+            }
+            FileFormat::Env => {
+                let mut input = String::new();
+                reader.read_to_string(&mut input)?;
+                return anyhow::Ok(vec![env::parse(&input)?.to_string()]);
+            }
+            FileFormat::Csv | FileFormat::Tsv => {
+                return anyhow::Ok(vec![tabular::read(reader, self.delimiter())?.to_string()]);
+            }
+            FileFormat::Xml => {
+                let value = xml::read_to_json(BufReader::new(reader))?;
+                return anyhow::Ok(vec![value.to_string()]);
+            }
+            FileFormat::Cson => {
+                let mut input = String::new();
+                reader.read_to_string(&mut input)?;
+                return anyhow::Ok(vec![cson::parse(&input)?.to_string()]);
+            }
+            FileFormat::Json5 => {
+                let mut input = String::new();
+                reader.read_to_string(&mut input)?;
+                return anyhow::Ok(vec![json5::parse(&input)?.to_string()]);
+            }
+            FileFormat::MessagePack => {
+                let mut input = Vec::<u8>::new();
+                reader.read_to_end(&mut input)?;
+                let values = message_pack::read(&input, limit)?;
+                return anyhow::Ok(values.iter().map(ToString::to_string).collect());
+            }
+            FileFormat::Raw => anyhow::bail!("raw is an output format only"),
         }
         anyhow::Ok(vec![String::from_utf8(json)?])
     }
 
+    // no multi document support
+    fn ensure_single_document(self, values: &[String]) -> anyhow::Result<()> {
+        if values.len() > 1 {
+            anyhow::bail!(
+                "received more than one output document, but {} does not support that.",
+                self.to_extension()
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that serialized output can be read back in this format.
+    fn validate_output(self, serialized: &[u8]) -> anyhow::Result<()> {
+        self.read_to_json(Cursor::new(serialized), &ReadOptions::default())
+            .map_err(|err| {
+                anyhow::anyhow!("produced invalid {} output: {}", self.to_extension(), err)
+            })?;
+        Ok(())
+    }
+
     fn write_format<W: Write>(
         self,
         values: &[String],
-        pretty: bool,
+        options: &WriteOptions,
         mut writer: &mut W,
     ) -> anyhow::Result<()> {
         match self {
-            // need to validate that the output is actually json
-            FileFormat::Json => {
-                for value in values {
-                    let mut de = serde_json::Deserializer::from_reader(Cursor::new(value));
-                    if pretty {
-                        let mut se = serde_json::Serializer::pretty(&mut writer);
-                        serde_transcode::transcode(&mut de, &mut se)?;
-                    } else {
-                        let mut se = serde_json::Serializer::new(&mut writer);
-                        serde_transcode::transcode(&mut de, &mut se)?;
-                    }
-                    writer.write_all(b"\n")?;
-                }
-            }
+            // json is valid json5, so comments of the input are dropped
+            FileFormat::Json | FileFormat::Json5 => write_json(values, options, writer)?,
             FileFormat::Yaml => {
-                let prefix = if values.len() > 1 { "---\n" } else { "" };
-                for value in values {
-                    writer.write_all(prefix.as_bytes())?;
-                    let mut de = serde_json::Deserializer::from_reader(Cursor::new(value));
+                for (idx, value) in values.iter().enumerate() {
+                    if options.output_separator.is_some() {
+                        if idx > 0 {
+                            writer.write_all(options.separator().as_bytes())?;
+                        }
+                    } else if values.len() > 1 || options.yaml_explicit_start {
+                        writer.write_all(b"---\n")?;
+                    }
+                    if let Some(emitter) = options.yaml_emitter() {
+                        let value = serde_json::from_str(value)?;
+                        writer.write_all(emitter.emit(&value).as_bytes())?;
+                        continue;
+                    }
+                    let mut de = serde_json::Deserializer::from_str(value);
                     let mut se = serde_yaml::Serializer::new(&mut writer);
                     serde_transcode::transcode(&mut de, &mut se)?;
                 }
             }
             FileFormat::Ron => {
-                // no multi document support
-                if values.len() > 1 {
-                    anyhow::bail!(
-                        "received more than one output document, but ron does not support that."
-                    );
-                }
+                let wrapped;
+                let values = if options.ron_array && values.len() > 1 {
+                    wrapped = [format!("[{}]", values.join(","))];
+                    &wrapped[..]
+                } else {
+                    values
+                };
+                self.ensure_single_document(values)?;
                 for value in values {
-                    let mut de = serde_json::Deserializer::from_reader(Cursor::new(value));
-                    let pretty_conf = if pretty {
-                        Some(ron::ser::PrettyConfig::default())
-                    } else {
-                        None
-                    };
-                    let mut se = ron::Serializer::with_options(
-                        &mut writer,
-                        pretty_conf,
-                        ron::Options::default(),
-                    )?;
-                    serde_transcode::transcode(&mut de, &mut se)?;
-                    writer.write_all(b"\n")?;
+                    write_ron(value, options, &mut writer)?;
                 }
             }
             FileFormat::Toml => {
-                // no multi document support
-                if values.len() > 1 {
-                    anyhow::bail!(
-                        "received more than one output document, but toml does not support that."
-                    );
+                let wrapped;
+                let values = match &options.toml_array {
+                    Some(key) => {
+                        wrapped = [wrap_toml_array(values, key)?];
+                        &wrapped[..]
+                    }
+                    None if values.len() > 1 => {
+                        wrapped = [wrap_toml_array(values, DEFAULT_TOML_ARRAY)?];
+                        &wrapped[..]
+                    }
+                    None => values,
+                };
+                self.ensure_single_document(values)?;
+                for value in values {
+                    write_toml(value, options, &mut writer)?;
+                }
+            }
+            FileFormat::Plist => {
+                self.ensure_single_document(values)?;
+                for value in values {
+                    let value = serde_json::from_str(value)?;
+                    property_list::write_json(value, options.plist_binary, &mut writer)?;
+                    if !options.plist_binary {
+                        writer.write_all(b"\n")?;
+                    }
+                }
+            }
+            FileFormat::Env => {
+                self.ensure_single_document(values)?;
+                for value in values {
+                    let value = serde_json::from_str(value)?;
+                    env::write(&value, options.env_export, options.env_sort, &mut writer)?;
+                }
+            }
+            // all documents share a single header
+            FileFormat::Csv | FileFormat::Tsv => {
+                let values = values
+                    .iter()
+                    .map(|value| serde_json::from_str(value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                tabular::write_rows(&tabular::rows(values)?, self.delimiter(), &mut writer)?;
+            }
+            FileFormat::Xml => anyhow::bail!("xml output not supported"),
+            FileFormat::Cson => {
+                self.ensure_single_document(values)?;
+                for value in values {
+                    writer.write_all(cson::write(&serde_json::from_str(value)?).as_bytes())?;
                 }
+            }
+            // documents are concatenated into a single stream
+            FileFormat::MessagePack => {
                 for value in values {
-                    let mut de = serde_json::Deserializer::from_reader(Cursor::new(value));
-                    let mut toml = String::new();
-                    let se = if pretty {
-                        toml::Serializer::pretty(&mut toml)
-                    } else {
-                        toml::Serializer::new(&mut toml)
-                    };
-                    serde_transcode::transcode(&mut de, se)?;
-                    writer.write_all(toml.as_bytes())?;
+                    message_pack::write(&serde_json::from_str(value)?, &mut writer)?;
                 }
             }
+            FileFormat::Raw => {
+                let texts: Vec<String> = values.iter().map(|value| pop_quotes(value)).collect();
+                write_raw(&texts, options, writer)?;
+            }
         }
         anyhow::Ok(())
     }
 }
 
-struct JsonDocuments {
+/// Writes json documents joined by the separator.
+fn write_json<W: Write>(
+    values: &[String],
+    options: &WriteOptions,
+    mut writer: &mut W,
+) -> anyhow::Result<()> {
+    for (idx, value) in values.iter().enumerate() {
+        if idx > 0 {
+            writer.write_all(options.separator().as_bytes())?;
+        }
+        let indent = options.indent.unwrap_or(2);
+        if indent > 0 && options.pretty(value)? {
+            let indent = if options.tab {
+                "\t".to_owned()
+            } else {
+                " ".repeat(indent)
+            };
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            let mut de = serde_json::Deserializer::from_str(value);
+            let mut se = serde_json::Serializer::with_formatter(&mut writer, formatter);
+            serde_transcode::transcode(&mut de, &mut se)?;
+        } else {
+            // documents are compact json already, as jq and serde_json
+            // produce it, so they are only validated instead of transcoded
+            serde_json::from_str::<serde::de::IgnoredAny>(value)?;
+            writer.write_all(value.trim_end().as_bytes())?;
+        }
+    }
+    if !values.is_empty() {
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Writes a single document as toml, which can not contain null.
+fn write_toml<W: Write>(value: &str, options: &WriteOptions, writer: &mut W) -> anyhow::Result<()> {
+    let mut de = serde_json::Deserializer::from_str(value);
+    let mut toml = String::new();
+    let pretty = options.pretty(value)?;
+    let se = if pretty && options.toml_style.is_none() {
+        toml::Serializer::pretty(&mut toml)
+    } else {
+        toml::Serializer::new(&mut toml)
+    };
+    if let Err(err) = serde_transcode::transcode(&mut de, se) {
+        // toml has no null, which the serializer reports as unit type
+        let value = serde_json::from_str(value)?;
+        if let Some((path, _)) = paths::leaf_paths(&value)
+            .into_iter()
+            .find(|(_, leaf)| leaf.is_null())
+        {
+            anyhow::bail!("toml can not represent null, found at {}", path);
+        }
+        return Err(err.into());
+    }
+    if let Some(style) = options.toml_style {
+        let multiline_arrays = pretty && style.multiline_arrays;
+        let style = toml_style::Style {
+            multiline_arrays,
+            ..style
+        };
+        toml = toml_style::format(&toml, style)?;
+    }
+    // the serializer output may or may not end with a newline
+    let toml = toml.trim_end_matches('\n');
+    if !toml.is_empty() {
+        writeln!(writer, "{toml}")?;
+    }
+    Ok(())
+}
+
+fn write_ron<W: Write>(value: &str, options: &WriteOptions, writer: &mut W) -> anyhow::Result<()> {
+    let mut de = serde_json::Deserializer::from_str(value);
+    let pretty_conf = if options.pretty(value)? {
+        Some(ron::ser::PrettyConfig::default())
+    } else {
+        None
+    };
+    let mut se = ron::Serializer::with_options(&mut *writer, pretty_conf, ron::Options::default())?;
+    serde_transcode::transcode(&mut de, &mut se)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Key of the array of tables holding multiple toml output documents,
+/// when --toml-array is not given. A single document is written as is.
+const DEFAULT_TOML_ARRAY: &str = "documents";
+
+/// Collects all documents into an array of tables under key, so multiple
+/// documents can be written as a single toml document. A sole array
+/// document, e.g. produced by --slurp, is used as the array itself.
+fn wrap_toml_array(values: &[String], key: &str) -> anyhow::Result<String> {
+    let mut docs = values
+        .iter()
+        .map(|value| serde_json::from_str(value))
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    let array = match docs.pop() {
+        Some(serde_json::Value::Array(array)) if docs.is_empty() => array,
+        Some(last) => {
+            docs.push(last);
+            docs
+        }
+        None => docs,
+    };
+    let mut table = serde_json::Map::new();
+    table.insert(key.to_owned(), serde_json::Value::Array(array));
+    Ok(serde_json::Value::Object(table).to_string())
+}
+
+/// Options influencing how output documents are serialized.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Debug, Default)]
+struct WriteOptions {
+    pretty: bool,
+    pretty_threshold: Option<usize>,
+    indent: Option<usize>,
+    tab: bool,
+    toml_array: Option<String>,
+    ron_array: bool,
+    toml_style: Option<toml_style::Style>,
+    plist_binary: bool,
+    yaml_quote: yaml::QuoteStyle,
+    yaml_explicit_start: bool,
+    yaml_anchors: bool,
+    yaml_align: bool,
+    prefix: String,
+    suffix: String,
+    env_export: bool,
+    env_sort: bool,
+    sort_keys: Option<sort::KeyOrder>,
+    output_separator: Option<String>,
+    dedup: Option<Dedup>,
+    nth: Option<usize>,
+    keys_only: bool,
+    flatten: Option<flatten::Style>,
+    unflatten: Option<flatten::Style>,
+    no_control_chars: bool,
+    validate_output: bool,
+    humanize_numbers: bool,
+    numeric_output: bool,
+    wrap: Option<usize>,
+    raw0: bool,
+}
+
+impl WriteOptions {
+    /// The emitter for yaml output, which is only used when the layout of
+    /// `serde_yaml` is customized.
+    fn yaml_emitter(&self) -> Option<yaml::Emitter> {
+        if self.yaml_quote == yaml::QuoteStyle::Auto && !self.yaml_anchors && !self.yaml_align {
+            return None;
+        }
+        let emitter = yaml::Emitter::new(self.yaml_quote)
+            .with_anchors(self.yaml_anchors)
+            .with_alignment(self.yaml_align);
+        Some(emitter)
+    }
+
+    /// Whether a document is pretty-printed, which depends on the size of
+    /// its compact form when a threshold is given.
+    fn pretty(&self, value: &str) -> anyhow::Result<bool> {
+        if self.pretty {
+            return Ok(true);
+        }
+        let Some(threshold) = self.pretty_threshold else {
+            return Ok(false);
+        };
+        let compact = serde_json::from_str::<serde_json::Value>(value)?.to_string();
+        Ok(compact.len() > threshold)
+    }
+
+    /// Separator between two documents of stream capable formats.
+    fn separator(&self) -> &str {
+        self.output_separator.as_deref().unwrap_or("\n")
+    }
+}
+
+/// The documents of a single input as json together with the format
+/// they were read from.
+pub struct JsonDocuments {
     jsons: Vec<String>,
     input_format: FileFormat,
 }
@@ -178,26 +535,191 @@ impl JsonDocuments {
             input_format,
         }
     }
+
+    /// The documents serialized as json.
+    #[must_use]
+    pub fn jsons(&self) -> &[String] {
+        &self.jsons
+    }
+
+    /// The format the documents were read from, which was guessed
+    /// when no format was given.
+    #[must_use]
+    pub fn input_format(&self) -> FileFormat {
+        self.input_format
+    }
+}
+
+/// Reads all documents of the reader. The format is guessed, when none
+/// is given.
+/// ```
+/// let docs = nuq::read_documents(std::io::Cursor::new("a: 1"), None)?;
+/// assert_eq!(docs.input_format(), nuq::FileFormat::Yaml);
+/// assert_eq!(docs.jsons(), [r#"{"a":1}"#]);
+/// # anyhow::Ok(())
+/// ```
+/// # Errors
+/// When reading fails or the input has an unsupported format.
+pub fn read_documents<R: Read + 'static>(
+    reader: R,
+    format: Option<FileFormat>,
+) -> anyhow::Result<JsonDocuments> {
+    let mut input = Input {
+        reader: Box::new(reader),
+        ext: String::new(),
+        format,
+        name: None,
+    };
+    input.read_to_docs(&ReadOptions::default())
 }
 
 struct Input {
     reader: Box<dyn Read>,
     ext: String,
     format: Option<FileFormat>,
+    // path of the input file for diagnostics
+    name: Option<String>,
+}
+
+/// Options influencing how input documents are read.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Default)]
+struct ReadOptions {
+    explain_guess: bool,
+    use_modeline: bool,
+    input_limit: Option<usize>,
+    strict_json: bool,
+    strip_ansi: bool,
+    base64_decode: bool,
+    raw_input: bool,
+}
+
+/// Formats tried in order when guessing the input format.
+const GUESS_ORDER: [FileFormat; 9] = [
+    FileFormat::Json,
+    FileFormat::Json5,
+    // any xml is a yaml string as well
+    FileFormat::Xml,
+    FileFormat::Yaml,
+    // simple cson documents are valid yaml, so they keep being read as yaml
+    FileFormat::Cson,
+    FileFormat::Toml,
+    FileFormat::Ron,
+    FileFormat::Plist,
+    // almost any text is csv, so it is tried last
+    FileFormat::Csv,
+];
+
+/// Formats tried when guessing the format of content. Nearly every byte
+/// sequence is a message pack stream, so it is only tried for binary
+/// content, which no text format accepts.
+fn guess_order(content: &[u8]) -> Vec<FileFormat> {
+    let mut order = GUESS_ORDER.to_vec();
+    if std::str::from_utf8(content).is_err() {
+        order.push(FileFormat::MessagePack);
+    }
+    order
+}
+
+/// Describes the result of every guess candidate for content.
+fn explain_guess<W: Write>(
+    content: &[u8],
+    options: &ReadOptions,
+    writer: &mut W,
+) -> anyhow::Result<()> {
+    let mut guessed = None;
+    for format in guess_order(content) {
+        match format.read_to_json(Cursor::new(content), options) {
+            Ok(jsons) => {
+                writeln!(
+                    writer,
+                    "{}: ok, {} document(s)",
+                    format.to_extension(),
+                    jsons.len()
+                )?;
+                guessed = guessed.or(Some(format));
+            }
+            Err(err) => writeln!(writer, "{}: {}", format.to_extension(), err)?,
+        }
+    }
+    match guessed {
+        Some(format) => writeln!(writer, "guessed {}", format.to_extension())?,
+        None => writeln!(writer, "no format matched")?,
+    }
+    Ok(())
 }
 
 impl Input {
-    fn read_to_docs(&mut self) -> anyhow::Result<JsonDocuments> {
+    fn open(path: &Path, format: Option<FileFormat>) -> anyhow::Result<Input> {
+        let file = File::open(path).map_err(|err| FileError {
+            file: path.display().to_string(),
+            source: err.into(),
+        })?;
+        // compressed files are read transparently, so the extension
+        // before .gz determines the format
+        let (reader, ext): (Box<dyn Read>, _) = if path.extension().is_some_and(|ext| ext == "gz") {
+            let inner = path.with_extension("");
+            (
+                Box::new(flate2::read::GzDecoder::new(file)),
+                ext_from_path(inner)?,
+            )
+        } else {
+            // an explicit format makes the extension optional
+            let ext = match ext_from_path(path) {
+                Err(_) if format.is_some() => String::new(),
+                ext => ext?,
+            };
+            (Box::new(file), ext)
+        };
+        Ok(Input {
+            reader,
+            ext,
+            format,
+            name: Some(path.display().to_string()),
+        })
+    }
+
+    /// The format given explicitly or by extension, if it is known.
+    fn known_format(&self) -> Option<FileFormat> {
+        self.format
+            .or_else(|| FileFormat::from_extension(&self.ext).ok())
+    }
+
+    fn read_to_docs(&mut self, options: &ReadOptions) -> anyhow::Result<JsonDocuments> {
+        if options.strip_ansi {
+            let mut content = Vec::<u8>::new();
+            self.reader.read_to_end(&mut content)?;
+            self.reader = Box::new(Cursor::new(ansi::strip(&content)));
+        }
+        if options.base64_decode {
+            let mut content = Vec::<u8>::new();
+            self.reader.read_to_end(&mut content)?;
+            content.retain(|byte| !byte.is_ascii_whitespace());
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(content)
+                .map_err(|err| anyhow::anyhow!("failed to decode base64 input: {}", err))?;
+            self.reader = Box::new(Cursor::new(decoded));
+        }
+        if options.raw_input {
+            let mut content = String::new();
+            self.reader.read_to_string(&mut content)?;
+            let lines = content
+                .lines()
+                .take(options.input_limit.unwrap_or(usize::MAX))
+                .map(|line| serde_json::Value::from(line).to_string())
+                .collect();
+            return Ok(JsonDocuments::new(lines, FileFormat::Json));
+        }
         if let Some(format) = self.format {
             return Ok(JsonDocuments::new(
-                format.read_to_json(&mut self.reader)?,
+                format.read_to_json(&mut self.reader, options)?,
                 format,
             ));
         }
         if !self.ext.is_empty() {
             let format = FileFormat::from_extension(&self.ext)?;
             return Ok(JsonDocuments::new(
-                format.read_to_json(&mut self.reader)?,
+                format.read_to_json(&mut self.reader, options)?,
                 format,
             ));
         }
@@ -205,353 +727,2851 @@ impl Input {
         // we need to seek, so read to bytes
         let mut content = Vec::<u8>::new();
         self.reader.read_to_end(&mut content)?;
-        let formats = [
-            FileFormat::Json,
-            FileFormat::Yaml,
-            FileFormat::Toml,
-            FileFormat::Ron,
-        ];
-        for format in formats {
-            if let Ok(jsons) = format.read_to_json(Cursor::new(&content)) {
-                return Ok(JsonDocuments::new(jsons, format));
+        let hinted = std::str::from_utf8(&content)
+            .ok()
+            .filter(|_| options.use_modeline)
+            .and_then(modeline::file_type)
+            .and_then(|file_type| FileFormat::from_extension(&file_type).ok());
+        if let Some(format) = hinted {
+            return Ok(JsonDocuments::new(
+                format.read_to_json(Cursor::new(&content), options)?,
+                format,
+            ));
+        }
+        if options.explain_guess {
+            explain_guess(&content, options, &mut std::io::stderr().lock())?;
+        }
+        for format in guess_order(&content) {
+            match format.read_to_json(Cursor::new(&content), options) {
+                Ok(jsons) => return Ok(JsonDocuments::new(jsons, format)),
+                // json rejected by --strict-json must not be guessed as another format
+                Err(err)
+                    if format == FileFormat::Json
+                        && options.strict_json
+                        && format
+                            .read_to_json(Cursor::new(&content), &ReadOptions::default())
+                            .is_ok() =>
+                {
+                    return Err(err);
+                }
+                Err(_) => {}
             }
         }
         Err(anyhow::anyhow!("Input has an unsupported format"))
     }
 }
 
+/// Environment variable holding the default program.
+const PROGRAM_ENV: &str = "NUQ_PROGRAM";
+
 /// A multi-format frontend for jq
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Args {
-    /// Jq program to execute.
-    #[clap(value_parser)]
-    program: String,
+    /// Jq program to execute. Taken from the environment when omitted,
+    /// e.g. to configure a default filter in a profile, and . otherwise.
+    /// An existing file in place of the program is read as input if it
+    /// has a known extension or a format is given, so converting formats
+    /// needs no program.
+    #[clap(value_parser, env = PROGRAM_ENV, hide_env_values = true)]
+    program: Option<String>,
 
     /// Input files, stdin if omitted.
     #[clap(value_parser)]
     files: Vec<PathBuf>,
 
+    /// Applies the jq expression to every value of an object or array
+    /// input before running the program.
+    #[clap(long, value_parser)]
+    map_values: Option<String>,
+
+    /// Binds the string value to the variable $name in the program. Can be
+    /// given multiple times.
+    #[clap(long, value_names = ["NAME", "VALUE"], num_args = 2, action = clap::ArgAction::Append)]
+    arg: Vec<String>,
+
+    /// Binds the json value to the variable $name in the program, e.g. a
+    /// number or an object. Can be given multiple times.
+    #[clap(long, value_names = ["NAME", "JSON"], num_args = 2, action = clap::ArgAction::Append)]
+    argjson: Vec<String>,
+
+    /// Binds an array of all documents of the file to the variable $name
+    /// in the program. The format follows the file extension, so yaml or
+    /// toml files work as well. Can be given multiple times.
+    #[clap(long, value_names = ["NAME", "PATH"], num_args = 2, action = clap::ArgAction::Append)]
+    slurpfile: Vec<String>,
+
+    /// Converts object inputs into arrays of key and value objects before
+    /// running the program.
+    #[clap(long, action)]
+    entries: bool,
+
+    /// Converts arrays of key and value objects into objects before running
+    /// the program. This is the inverse of --entries.
+    #[clap(long, action)]
+    from_entries: bool,
+
+    /// Outputs null instead of failing, when the program errors, e.g. by
+    /// indexing into a missing nested field. This swallows all errors of
+    /// the program and stops at the first error of a document.
+    #[clap(long, action)]
+    null_on_missing: bool,
+
+    /// Keeps processing the remaining documents when the program fails on
+    /// a document and reports all failures at the end. Missing keys of
+    /// --required-keys are only warned about.
+    #[clap(long, action)]
+    collect_errors: bool,
+
+    /// Format of errors written to stderr. Json writes every error as a
+    /// single line object with the fields level, message and file, e.g.
+    /// to be parsed by CI systems.
+    #[clap(long, value_parser, value_enum, default_value_t)]
+    log_format: LogFormat,
+
+    /// Aborts when a single run of the jq program takes longer than the
+    /// given duration, e.g. 500ms, 10s or 1m. Best effort, as a running jq
+    /// program can not be interrupted and is abandoned instead.
+    #[clap(long, value_parser = worker::parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Splits stdin on lines consisting of === and guesses the format of
+    /// every chunk independently.
+    #[clap(long, action)]
+    multi_format: bool,
+
+    /// Only processes inputs of the given format and skips all others,
+    /// e.g. to handle the yaml files out of many mixed files.
+    #[clap(long, value_parser = readable_format())]
+    only: Option<FileFormat>,
+
+    /// Virtual file name of stdin, whose extension determines the input
+    /// format like the extension of an input file.
+    #[clap(long, value_parser)]
+    stdin_filename: Option<PathBuf>,
+
+    /// Reads every file of a tar archive as a separate input after the
+    /// input files. The format of a file is derived from its extension.
+    #[cfg(feature = "tar")]
+    #[clap(long, value_parser)]
+    tar: Option<PathBuf>,
+
+    /// Only process the first N input documents across all inputs.
+    #[clap(long, value_parser)]
+    head: Option<usize>,
+
+    /// Only read the first N documents of every input.
+    #[clap(long, value_parser)]
+    input_limit: Option<usize>,
+
+    /// Only process the last N input documents across all inputs.
+    #[clap(long, value_parser)]
+    tail: Option<usize>,
+
+    /// Only process up to N randomly chosen input documents.
+    #[clap(long, value_parser)]
+    random_sample: Option<usize>,
+
+    /// Fails when an input document lacks any of the comma separated dot
+    /// paths, e.g. metadata.name,spec.replicas. Numbers index into arrays.
+    #[clap(long, value_parser, value_delimiter = ',', value_name = "PATHS")]
+    required_keys: Vec<String>,
+
+    /// Only process input documents of the given top level type.
+    #[clap(long = "type", value_parser, value_enum)]
+    type_filter: Option<JsonType>,
+
+    /// Seed for random operations to make them reproducible.
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
+
+    /// Randomly reorders the outputs of all inputs, e.g. together with
+    /// --random-sample to build randomized fixtures. The documents of all
+    /// inputs are combined, so the output has the format of the first input.
+    #[clap(long, action)]
+    shuffle: bool,
+
+    /// Sorts all input documents by the value at the given jq path, e.g.
+    /// .metadata.name, before running the program. Documents of all inputs
+    /// are buffered and combined, like with --concat-raw.
+    #[clap(long, value_parser)]
+    sort_by: Option<String>,
+
+    /// Sorts descending with --sort-by.
+    #[clap(long, action)]
+    reverse: bool,
+
+    /// Replaces all input documents by a single object, which maps every
+    /// distinct value at the given jq path to the number of documents
+    /// with that value, e.g. .status.
+    #[clap(long, value_parser)]
+    count_by: Option<String>,
+
     /// Input format, will be guessed by extension or content.
-    #[clap(short, long, value_parser, value_enum)]
+    #[clap(short, long, value_parser = readable_format())]
     input_format: Option<FileFormat>,
 
+    /// Runs the program once with null as input instead of reading any
+    /// input, e.g. to generate documents. The output is json by default.
+    #[clap(short, long, action)]
+    null_input: bool,
+
+    /// Reads every line of the inputs as a json string instead of parsing
+    /// them. With --slurp all lines become a single array.
+    #[clap(short = 'R', long, action, conflicts_with_all = ["input_format", "null_input"])]
+    raw_input: bool,
+
     /// Output format, if omitted will return the input format.
-    /// Toml output may require reordering the input.
-    #[clap(short, long, value_parser, value_enum)]
+    /// Toml output may require reordering the input and fails on null
+    /// values, which toml can not represent. If jq outputs a JSON
+    /// string, raw only outputs the contained plain text. This
+    /// post-processes the jq output, so it may not behave the same as
+    /// "jq -r".
+    #[clap(
+        short,
+        long,
+        value_parser,
+        value_enum,
+        default_value_if("raw", clap::builder::ArgPredicate::IsPresent, "raw")
+    )]
     output_format: Option<FileFormat>,
 
-    /// If jq outputs a JSON string only output contained plain text.
-    /// This post-processes the jq output, so it may not behave the same
-    /// as "jq -r".
-    #[clap(short, long, action)]
+    /// Sets input and output format at once, e.g. to reformat
+    /// extensionless input. --input-format and --output-format take
+    /// precedence.
+    #[clap(long, value_parser = readable_format())]
+    format: Option<FileFormat>,
+
+    /// Shortcut for --output-format raw.
+    #[clap(short, long, action, conflicts_with = "output_format")]
     raw: bool,
 
+    /// Terminates each raw output with a NUL byte instead of a newline,
+    /// e.g. for xargs -0. Requires --raw.
+    #[clap(short = '0', long, action)]
+    raw0: bool,
+
+    /// Text written before each raw output. Requires --raw.
+    #[clap(long, value_parser)]
+    prefix: Option<String>,
+
+    /// Text written after each raw output. Requires --raw.
+    #[clap(long, value_parser)]
+    suffix: Option<String>,
+
+    /// Writes the raw outputs of all inputs as a single stream, so
+    /// separators are also placed between the outputs of different inputs.
+    /// Requires --raw.
+    #[clap(long, action)]
+    concat_raw: bool,
+
+    /// Converts strings of output documents, which are valid json numbers,
+    /// into numbers, e.g. after reading csv or env input. Strings, which
+    /// would not be written back the same, are kept, like zip codes with
+    /// leading zeros, exponents or integers too large to be exact.
+    #[clap(long, action)]
+    numeric_output: bool,
+
+    /// Groups the digits of integer outputs by thousands for display,
+    /// e.g. 1,000,000. Requires --raw.
+    #[clap(long, action)]
+    humanize_numbers: bool,
+
+    /// Wraps raw output lines at the given number of columns. Lines are
+    /// broken at spaces and longer words are split. Requires --raw.
+    #[clap(long, value_parser)]
+    wrap: Option<usize>,
+
+    /// Treat every element of an array input document as a separate
+    /// document. This is the inverse of --slurp.
+    #[clap(long, action)]
+    explode: bool,
+
+    /// Treat every entry of an object input document as a separate
+    /// document consisting of only this key and its value.
+    #[clap(long, action)]
+    explode_keys: bool,
+
     /// Concatenate all input files into a JSON array before processing it
     /// with jq.
     #[clap(long, action)]
     slurp: bool,
 
+    /// Deep merge all input documents into a single document before
+    /// processing it with jq. Later documents take precedence.
+    #[clap(long, action)]
+    merge_deep: bool,
+
+    /// How arrays are combined by --merge-deep. Union skips elements
+    /// already present in the earlier array.
+    #[clap(long, value_parser, value_enum, default_value_t)]
+    merge_arrays: merge::ArrayMerge,
+
     /// Enables or disables colored output. By default coloring is enabled
-    /// when writing to a tty.
+    /// when writing to a tty, unless colors are disabled by the environment
+    /// as described on no-color.org.
     #[clap(short, long, action)]
     color: Option<bool>,
 
+    /// Syntax highlighting theme of colored output. Defaults to
+    /// base16-ocean.dark.
+    #[clap(long, value_parser)]
+    theme: Option<String>,
+
+    /// Loads the syntax highlighting theme of colored output from a
+    /// .tmTheme file.
+    #[clap(long, value_parser)]
+    theme_file: Option<PathBuf>,
+
+    /// Number of colors used for colored output, when the terminal does
+    /// not support 24-bit colors.
+    #[clap(long, value_parser, value_enum, default_value_t)]
+    color_depth: highlight::ColorDepth,
+
     /// Pretty-prints the output, if the serializer supports that.
     #[clap(short, long, action)]
     pretty: bool,
-}
 
-impl Args {
-    fn make_inputs(&self) -> anyhow::Result<Vec<Input>> {
-        if self.files.is_empty() {
-            return Ok(vec![Input {
-                ext: String::new(),
-                reader: Box::new(std::io::stdin()),
-                format: self.input_format,
-            }]);
-        }
-        let mut readers = Vec::<Input>::new();
-        for path in &self.files {
-            readers.push(Input {
-                reader: Box::new(File::open(path)?),
-                ext: ext_from_path(path)?,
-                format: self.input_format,
-            });
-        }
-        Ok(readers)
-    }
+    /// Disables pretty-printing, taking precedence over --pretty and
+    /// --pretty-threshold. Json and ron documents are written on a single
+    /// line then, while line based formats like yaml keep their layout.
+    #[clap(short = 'm', long, action)]
+    compact: bool,
 
-    fn should_color(&self, format: Option<FileFormat>) -> bool {
-        match self.color {
-            Some(should) => should,
-            None => {
-                if std::io::stdout().is_terminal() {
-                    format.is_some()
-                } else {
-                    false
-                }
-            }
-        }
-    }
-}
+    /// Number of spaces per level of pretty-printed json output. Zero
+    /// writes compact json.
+    #[clap(long, value_parser)]
+    indent: Option<usize>,
 
-/// Turns a jq string output into a raw string
-/// reverting quoting and escaping.
-fn pop_quotes(text: &str) -> String {
-    // check if the text starts with a quote
-    // if not its likely not a string returned
-    // by jq, so do nothing.
-    if !text.starts_with('"') {
-        return text.to_owned();
-    }
-    let count = text.chars().count();
-    // pop first and last quote
-    let mut immediate: String = text
-        .char_indices()
-        .filter(|(idx, char)| !(*idx == count - 2 && *char == '"'))
-        .filter(|(idx, char)| !(*idx == 0 && *char == '"'))
-        .map(|(_, char)| char)
-        .collect();
-    immediate = immediate.trim().to_owned();
-    immediate.push('\n');
-    // replace json escape sequence \" with "
-    immediate.replace("\\\"", "\"")
-}
+    /// Indents pretty-printed json output with a tab per level.
+    #[clap(long, action)]
+    tab: bool,
 
-struct Executor {
-    program: jq_rs::JqProgram,
-}
+    /// Only pretty-prints documents, whose compact form is longer than the
+    /// given number of bytes.
+    #[clap(long, value_parser)]
+    pretty_threshold: Option<usize>,
 
-impl Executor {
-    fn new(program: &str) -> anyhow::Result<Executor> {
-        let program = jq_rs::compile(program).map_err(|err| anyhow::anyhow!("{}", err))?;
-        Ok(Self { program })
-    }
+    /// Suppress output documents equal to the previous output document.
+    #[clap(long, action)]
+    dedup: bool,
 
-    fn execute<W: Write>(
-        &mut self,
-        jsons: &[String],
-        output_format: Option<FileFormat>,
-        pretty: bool,
-        writer: &mut W,
-    ) -> anyhow::Result<()> {
-        let outputs: anyhow::Result<Vec<String>> = jsons
-            .iter()
-            .map(|j| {
-                let output = self
-                    .program
-                    .run(j)
-                    .map_err(|err| anyhow::anyhow!("failed to execute jq program: {}", err))?;
-                match output_format {
-                    Some(_) => Ok(output),
-                    None => Ok(pop_quotes(&output)),
-                }
-            })
-            .collect();
-        let outputs = outputs?;
-        match output_format {
-            Some(format) => format
-                .write_format(&outputs, pretty, writer)
-                .map_err(|err| anyhow::anyhow!("failed to produce output: {}", err))?,
-            None => {
-                for output in outputs {
-                    writer.write_all(output.as_bytes())?;
-                }
+    /// Suppress output documents equal to any previous output document.
+    #[clap(long, action)]
+    dedup_global: bool,
+
+    /// Writes only the top level keys of every output document as array
+    /// in their original order instead of the data.
+    #[clap(long, action)]
+    keys_only: bool,
+
+    /// Flattens nested objects and arrays of every output document into a
+    /// single object with the paths of the leaves as keys, e.g. a.b.0.
+    #[clap(long, action)]
+    flatten: bool,
+
+    /// Rebuilds nested objects and arrays from output documents with
+    /// flattened keys. This is the inverse of --flatten.
+    #[clap(long, action)]
+    unflatten: bool,
+
+    /// Joins the keys of --flatten and splits the keys of --unflatten.
+    #[clap(long, value_parser, default_value = ".")]
+    flatten_separator: String,
+
+    /// Whether array indices of --flatten and --unflatten are joined like
+    /// keys or appended in brackets.
+    #[clap(long, value_parser, value_enum, default_value_t)]
+    flatten_array_style: flatten::ArrayStyle,
+
+    /// Only write the N-th (zero-based) output of the program per input.
+    /// Nothing is written, if there are fewer outputs.
+    #[clap(long, value_parser)]
+    nth: Option<usize>,
+
+    /// Written between two output documents of json, yaml and raw output
+    /// instead of a newline or the yaml document marker.
+    #[clap(long, value_parser)]
+    output_separator: Option<String>,
+
+    /// Sort object keys in natural order, so numeric keys are ordered
+    /// by their value.
+    #[clap(long, action)]
+    natural_sort: bool,
+
+    /// Sort object keys lexicographically in every output format, so the
+    /// output is stable across runs and diffs well.
+    #[clap(short = 'S', long, action)]
+    sort_keys: bool,
+
+    /// Do not keep the key order of the input, but order keys
+    /// lexicographically like a plain map. Keeping the order is cheap, as
+    /// objects are stored in insertion order anyway, while this requires an
+    /// additional sorting pass over every output document.
+    #[clap(long, action)]
+    no_preserve_order: bool,
+
+    /// Collect all output documents into an array of tables under the
+    /// given key, so multiple documents can be written as toml. Multiple
+    /// documents are collected under documents by default.
+    #[clap(long, value_parser)]
+    toml_array: Option<String>,
+
+    /// Collects multiple output documents into a single list, so they can
+    /// be written as ron. A single document is written as is.
+    #[clap(long, action)]
+    ron_array: bool,
+
+    /// Keeps arrays of pretty-printed toml output on a single line.
+    #[clap(long, action)]
+    toml_no_pretty_arrays: bool,
+
+    /// Number of spaces before every entry of a multi-line toml array.
+    #[clap(long, value_parser)]
+    toml_indent_entries: Option<usize>,
+
+    /// Pads the keys of toml output, so the equal signs of a table line up.
+    #[clap(long, action)]
+    toml_align_entries: bool,
+
+    /// Writes all toml strings as basic strings with escapes. By default
+    /// strings containing backslashes, e.g. windows paths or regular
+    /// expressions, are written as literal strings, which need no escaping.
+    #[clap(long, action)]
+    toml_basic_strings: bool,
+
+    /// Quoting style of strings in yaml output. Plain falls back to double
+    /// quotes for strings, which would change their meaning otherwise.
+    #[clap(long, value_parser, value_enum, default_value_t)]
+    yaml_quote: yaml::QuoteStyle,
+
+    /// Writes repeated identical objects and arrays of yaml output only once
+    /// with an anchor and refers to them by aliases afterwards. Anchors of
+    /// yaml input are expanded otherwise.
+    #[clap(long, action)]
+    yaml_anchors: bool,
+
+    /// Aligns the scalar values of every yaml mapping at a common column
+    /// for readability.
+    #[clap(long, action)]
+    yaml_align: bool,
+
+    /// Canonical yaml output as used for Kubernetes manifests: sorted keys,
+    /// block style, quotes only for ambiguous strings and a document
+    /// separator before every document. Implies --output-format yaml.
+    #[clap(long, visible_alias = "canonical-yaml", action)]
+    k8s: bool,
+
+    /// Prefix every line of env output with export.
+    #[clap(long, action)]
+    env_export: bool,
+
+    /// Sort the keys of env output.
+    #[clap(long, action)]
+    env_sort: bool,
+
+    /// Write plist output in the binary instead of the xml format.
+    /// Binary output is never colored.
+    #[clap(long, action)]
+    plist_binary: bool,
+
+    /// Omit the newline at the end of the output.
+    #[clap(long, action)]
+    no_trailing_newline: bool,
+
+    /// Flush the output after every document instead of buffering it. Json,
+    /// yaml and raw output is rendered document by document for that, other
+    /// formats, --nth, --output-separator and diffs are flushed per input.
+    #[clap(long, action)]
+    unbuffered: bool,
+
+    /// Fail when an output string contains a control character other than
+    /// tab, line feed or carriage return.
+    #[clap(long, action)]
+    no_control_chars: bool,
+
+    /// Reads the serialized output back in its format before writing it,
+    /// to guarantee it is well-formed.
+    #[clap(long, action)]
+    validate_output: bool,
+
+    /// Remove ANSI escape sequences, e.g. colors, from the input before
+    /// parsing it.
+    #[clap(long, action)]
+    strip_ansi: bool,
+
+    /// Decodes every input from base64 before parsing it, e.g. for values
+    /// of kubernetes secrets.
+    #[clap(long, action)]
+    base64_decode: bool,
+
+    /// Encodes the complete output as base64.
+    #[clap(long, action)]
+    base64_encode: bool,
+
+    /// Reject json input containing duplicate object keys.
+    #[clap(long, action)]
+    strict_json: bool,
+
+    /// Reports the result of every candidate format to stderr when the
+    /// input format needs to be guessed.
+    #[clap(long, action)]
+    explain_guess: bool,
+
+    /// Uses the file type of a vim or emacs modeline, e.g. "# vim: ft=toml",
+    /// in the first or last lines of an input without extension instead
+    /// of guessing its format.
+    #[clap(long, action)]
+    use_modeline: bool,
+
+    /// Writes the output to the given file instead of stdout. Without
+    /// --output-format the format follows the file extension. Colors are
+    /// only used, if requested explicitly.
+    #[clap(short = 'O', long, value_parser)]
+    output: Option<PathBuf>,
+
+    /// Reports whether --output would change the file instead of writing
+    /// it, e.g. to check batch reformatting first.
+    #[clap(long, action, requires = "output")]
+    dry_run: bool,
+
+    /// Additionally writes the output without colors to the given file.
+    #[clap(long, value_parser)]
+    tee: Option<PathBuf>,
+
+    /// Shows a unified diff between the input and the output of the program,
+    /// both in the output format, instead of the output.
+    #[clap(long, action)]
+    diff: bool,
+
+    /// Like --diff, but colors additions and deletions when coloring
+    /// is enabled.
+    #[clap(long, action)]
+    color_diff: bool,
+
+    /// Number of unchanged lines shown around every change of a diff.
+    #[clap(long, value_parser, default_value_t = 3)]
+    context_lines: usize,
+
+    /// Writes the program output as csv. Objects and arrays of objects
+    /// become rows, which are flattened like --flatten. The header is the
+    /// union of the keys of all rows and missing fields are left empty.
+    #[clap(long, action)]
+    to_csv: bool,
+
+    /// Only checks that all inputs parse and the program runs on them
+    /// without an error, e.g. in pre-commit hooks. Nothing is written on
+    /// success, failures are reported on stderr with a non-zero exit code.
+    #[clap(long, action)]
+    validate_only: bool,
+
+    /// Suppresses all output on stdout. Errors are still reported.
+    #[clap(short, long, action)]
+    quiet: bool,
+
+    /// Prints a one line summary of the structure and the detected format
+    /// of every input document instead of running the program.
+    #[clap(long, action)]
+    describe: bool,
+
+    /// Lists every leaf path of the jq output in jq notation together
+    /// with its value instead of the data itself.
+    #[clap(long, action)]
+    paths: bool,
+}
+
+impl Args {
+    fn make_inputs(&self) -> anyhow::Result<Vec<Input>> {
+        #[cfg(feature = "tar")]
+        let from_tar = self.tar.is_some();
+        #[cfg(not(feature = "tar"))]
+        let from_tar = false;
+        let files = self.files();
+        if files.is_empty() && !from_tar && self.multi_format {
+            let mut content = String::new();
+            std::io::stdin().read_to_string(&mut content)?;
+            return Ok(split_chunks(&content)
+                .into_iter()
+                .map(|chunk| Input {
+                    reader: Box::new(Cursor::new(chunk)),
+                    ext: String::new(),
+                    format: None,
+                    name: None,
+                })
+                .collect());
+        }
+        if files.is_empty() && !from_tar {
+            let ext = match &self.stdin_filename {
+                Some(name) => ext_from_path(name)?,
+                None => String::new(),
+            };
+            return Ok(vec![Input {
+                ext,
+                reader: Box::new(std::io::stdin()),
+                format: self.input_format(),
+                name: None,
+            }]);
+        }
+        let mut readers = Vec::<Input>::new();
+        for path in &files {
+            readers.push(Input::open(path, self.input_format())?);
+        }
+        #[cfg(feature = "tar")]
+        if let Some(tar) = &self.tar {
+            for (path, content) in archive::read_tar_file(tar)? {
+                readers.push(Input {
+                    reader: Box::new(Cursor::new(content)),
+                    ext: ext_from_path(&path)?,
+                    format: self.input_format(),
+                    name: Some(path.display().to_string()),
+                });
             }
         }
-        anyhow::Ok(())
+        if let Some(only) = self.only {
+            // guessed formats are only known after reading, while files
+            // with an unknown extension are skipped
+            readers.retain(|input| match input.known_format() {
+                Some(format) => format == only,
+                None => input.ext.is_empty(),
+            });
+        }
+        Ok(readers)
     }
-}
 
-/// Runs nuq
-/// # Errors
-/// When arg parsing, io, ... fails
-/// # Panics
-/// When the executor is somehow not initialized.
-pub fn run(args: &Args) -> anyhow::Result<()> {
-    if args.raw && args.output_format.is_some() {
-        anyhow::bail!("cannot use --raw with --output-format");
+    fn validate(&self) -> anyhow::Result<()> {
+        let names = self
+            .arg
+            .chunks(2)
+            .chain(self.argjson.chunks(2))
+            .chain(self.slurpfile.chunks(2))
+            .map(|pair| &pair[0]);
+        for name in names {
+            let mut chars = name.chars();
+            if !chars
+                .next()
+                .is_some_and(|char| char.is_ascii_alphabetic() || char == '_')
+                || !chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+            {
+                anyhow::bail!("invalid variable name {}", name);
+            }
+        }
+        if self.entries && self.from_entries {
+            anyhow::bail!("cannot use --entries with --from-entries");
+        }
+        if self.null_input && !self.files().is_empty() {
+            anyhow::bail!("cannot use --null-input with input files");
+        }
+        if self.flatten && self.unflatten {
+            anyhow::bail!("cannot use --flatten with --unflatten");
+        }
+        if self.flatten_separator.is_empty() {
+            anyhow::bail!("--flatten-separator must not be empty");
+        }
+        if self.theme.is_some() && self.theme_file.is_some() {
+            anyhow::bail!("cannot use --theme with --theme-file");
+        }
+        if self.tab && self.indent.is_some() {
+            anyhow::bail!("cannot use --tab with --indent");
+        }
+        if !self.raw() && (self.prefix.is_some() || self.suffix.is_some()) {
+            anyhow::bail!("--prefix and --suffix require --raw");
+        }
+        if self.k8s && self.output_format.is_some_and(|f| f != FileFormat::Yaml) {
+            anyhow::bail!("--k8s requires yaml output");
+        }
+        if self.multi_format && (self.input_format().is_some() || self.stdin_filename.is_some()) {
+            anyhow::bail!("cannot use --multi-format with --input-format or --stdin-filename");
+        }
+        if self.raw0 && !self.raw() {
+            anyhow::bail!("--raw0 requires --raw");
+        }
+        if self.raw0 && self.output_separator.is_some() {
+            anyhow::bail!("cannot use --raw0 with --output-separator");
+        }
+        if self.concat_raw && !self.raw() {
+            anyhow::bail!("--concat-raw requires --raw");
+        }
+        if self.reverse && self.sort_by.is_none() {
+            anyhow::bail!("--reverse requires --sort-by");
+        }
+        if self.humanize_numbers && !self.raw() {
+            anyhow::bail!("--humanize-numbers requires --raw");
+        }
+        match self.wrap {
+            Some(_) if !self.raw() => anyhow::bail!("--wrap requires --raw"),
+            Some(0) => anyhow::bail!("--wrap requires at least one column"),
+            _ => {}
+        }
+        if self.paths && self.output_format.is_some() {
+            anyhow::bail!("cannot use --paths with --raw or --output-format");
+        }
+        if (self.diff || self.color_diff) && (self.raw() || self.paths) {
+            anyhow::bail!("cannot use --diff with --raw or --paths");
+        }
+        if self.to_csv && (self.output_format.is_some() || self.paths || self.describe) {
+            anyhow::bail!("cannot use --to-csv with --raw, --output-format, --paths or --describe");
+        }
+        if self.describe && (self.raw() || self.paths || self.diff || self.color_diff) {
+            anyhow::bail!("cannot use --describe with --raw, --paths or --diff");
+        }
+        if (self.explode || self.explode_keys) && (self.slurp || self.merge_deep) {
+            anyhow::bail!("cannot use --explode or --explode-keys with --slurp or --merge-deep");
+        }
+        if self.slurp && self.merge_deep {
+            anyhow::bail!("cannot use --slurp with --merge-deep");
+        }
+        Ok(())
     }
-    let inputs = if args.slurp {
-        let array = slurp(&mut args.make_inputs()?)?;
-        vec![Input {
-            ext: String::new(),
-            reader: Box::new(Cursor::new(array)),
-            format: args.input_format,
-        }]
-    } else {
-        args.make_inputs()?
-    };
-    let mut executor = Executor::new(&args.program)?;
-    let styles = highlight::Styles::default();
-    for mut input in inputs {
-        let docs = input.read_to_docs()?;
-        let output_format = if args.raw {
-            None
+
+    fn raw(&self) -> bool {
+        self.output_format() == Some(FileFormat::Raw)
+    }
+
+    fn input_format(&self) -> Option<FileFormat> {
+        self.input_format.or(self.format)
+    }
+
+    /// The format written for documents read in the input format. None
+    /// stands for plain text output.
+    fn output_format_of(&self, input: FileFormat) -> Option<FileFormat> {
+        if self.raw() || self.paths || self.describe || self.to_csv {
+            return None;
+        }
+        Some(match self.output_format() {
+            None if self.k8s => FileFormat::Yaml,
+            explicit => resolve_output_format(explicit, self.output.as_deref(), input),
+        })
+    }
+
+    /// Splits the documents of an input into single documents with
+    /// --unbuffered, so each can be flushed once it is written. Only formats,
+    /// which write a stream of documents the same way one by one, are split.
+    /// Split documents are marked with true.
+    fn split_for_flushing(
+        &self,
+        docs: anyhow::Result<JsonDocuments>,
+    ) -> Vec<anyhow::Result<(JsonDocuments, bool)>> {
+        let streamable = |format| {
+            self.nth.is_none()
+                && self.output_separator.is_none()
+                && !(self.diff || self.color_diff || self.to_csv)
+                && matches!(
+                    self.output_format_of(format),
+                    None | Some(FileFormat::Json | FileFormat::Json5 | FileFormat::Yaml)
+                )
+        };
+        match docs {
+            Ok(docs)
+                if self.unbuffered && docs.jsons.len() > 1 && streamable(docs.input_format) =>
+            {
+                let format = docs.input_format;
+                docs.jsons
+                    .into_iter()
+                    .map(|json| Ok((JsonDocuments::new(vec![json], format), true)))
+                    .collect()
+            }
+            docs => vec![docs.map(|docs| (docs, false))],
+        }
+    }
+
+    fn output_format(&self) -> Option<FileFormat> {
+        self.output_format.or(self.format)
+    }
+
+    /// The toml layout, if it differs from the serializer's own.
+    fn toml_style(&self) -> Option<toml_style::Style> {
+        if !self.toml_no_pretty_arrays
+            && self.toml_indent_entries.is_none()
+            && !self.toml_align_entries
+            && !self.toml_basic_strings
+        {
+            return None;
+        }
+        let default = toml_style::Style::default();
+        Some(toml_style::Style {
+            multiline_arrays: !self.toml_no_pretty_arrays,
+            indent: self.toml_indent_entries.unwrap_or(default.indent),
+            align: self.toml_align_entries,
+            basic_strings: self.toml_basic_strings,
+        })
+    }
+
+    /// Variables given by --arg, --argjson and --slurpfile as pairs of name
+    /// and json value.
+    fn variables(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let mut variables = Vec::new();
+        for pair in self.arg.chunks(2) {
+            let value = serde_json::Value::String(pair[1].clone());
+            variables.push((pair[0].clone(), value.to_string()));
+        }
+        for pair in self.argjson.chunks(2) {
+            let value: serde_json::Value = serde_json::from_str(&pair[1]).map_err(|err| {
+                anyhow::anyhow!("invalid json for --argjson {}: {}", pair[0], err)
+            })?;
+            variables.push((pair[0].clone(), value.to_string()));
+        }
+        for pair in self.slurpfile.chunks(2) {
+            let mut input = Input::open(Path::new(&pair[1]), None)?;
+            let docs = input
+                .read_to_docs(&ReadOptions::default())
+                .map_err(|err| FileError {
+                    file: pair[1].clone(),
+                    source: err,
+                })?;
+            variables.push((pair[0].clone(), format!("[{}]", docs.jsons.join(","))));
+        }
+        Ok(variables)
+    }
+
+    /// Random numbers for sampling and shuffling, seeded by --seed.
+    fn rng(&self) -> sample::Rng {
+        self.seed
+            .map_or_else(sample::Rng::from_time, sample::Rng::new)
+    }
+
+    fn flatten_style(&self) -> flatten::Style {
+        flatten::Style {
+            separator: self.flatten_separator.clone(),
+            arrays: self.flatten_array_style,
+        }
+    }
+
+    /// Whether the first positional argument names an input file instead
+    /// of the program. A program from the environment is never a file.
+    /// Programs like keys may share the name of a file, so a file is only
+    /// assumed with a format argument or a known extension.
+    fn program_is_file(&self) -> bool {
+        self.program.as_deref().is_some_and(|program| {
+            let format_given = self.input_format.is_some()
+                || self.output_format.is_some()
+                || self.format.is_some();
+            let known_extension = ext_from_path(program)
+                .and_then(|ext| FileFormat::from_extension(&ext))
+                .is_ok();
+            (format_given || known_extension)
+                && Path::new(program).is_file()
+                && std::env::var(PROGRAM_ENV).ok().as_deref() != Some(program)
+        })
+    }
+
+    /// The program given as argument or by the environment, . otherwise.
+    fn program(&self) -> String {
+        match &self.program {
+            Some(program) if !self.program_is_file() => program.clone(),
+            _ => std::env::var(PROGRAM_ENV).unwrap_or_else(|_| ".".to_owned()),
+        }
+    }
+
+    /// The input files including a file given in place of the program.
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        if self.program_is_file() {
+            files.extend(self.program.iter().map(PathBuf::from));
+        }
+        files.extend(self.files.iter().cloned());
+        files
+    }
+
+    /// The jq program including the shortcuts composed with it.
+    fn jq_program(&self) -> anyhow::Result<String> {
+        let mut program = self.program();
+        if self.entries {
+            program = format!("to_entries | {program}");
+        }
+        if self.from_entries {
+            program = format!("from_entries | {program}");
+        }
+        if let Some(expr) = &self.map_values {
+            program = format!("map_values({expr}) | {program}");
+        }
+        if self.null_on_missing {
+            program = format!("try ({program}) catch null");
+        }
+        // variables are bound first, so they are visible in all shortcuts
+        for (name, value) in self.variables()?.iter().rev() {
+            program = format!("({value}) as ${name} | {program}");
+        }
+        Ok(program)
+    }
+
+    /// Reads the documents of all inputs and applies the document
+    /// selection options. Inputs are read one after another as the
+    /// documents are consumed, unless an option needs all documents at once.
+    fn read_documents(&self) -> anyhow::Result<Documents<'_>> {
+        if self.null_input {
+            let docs = JsonDocuments::new(vec!["null".to_owned()], FileFormat::Json);
+            return Ok(Box::new(std::iter::once(Ok(docs))));
+        }
+        let read_options = self.read_options();
+        let (inputs, read_options) = if self.slurp || self.merge_deep {
+            let combined = if self.slurp {
+                slurp(&mut self.make_inputs()?, &read_options)?
+            } else {
+                merge(&mut self.make_inputs()?, &read_options, self.merge_arrays)?
+            };
+            let combined = Input {
+                ext: String::new(),
+                reader: Box::new(Cursor::new(combined)),
+                format: self.input_format(),
+                name: None,
+            };
+            // the read options were applied to the inputs already
+            (vec![combined], ReadOptions::default())
         } else {
-            Some(match args.output_format {
-                Some(format) => format,
-                None => docs.input_format,
-            })
-        };
-        let mut writer: Box<dyn Write> = if args.should_color(output_format) {
-            Box::new(highlight::Writer::new(
-                std::io::stdout().lock(),
-                output_format.unwrap(),
-                &styles,
-            ))
+            (self.make_inputs()?, read_options)
+        };
+        let mut inputs = inputs.into_iter();
+        let mut count = 0;
+        let streamed = std::iter::from_fn(move || loop {
+            // no need to read further inputs once enough documents are read
+            if self.head.is_some_and(|head| count >= head) {
+                return None;
+            }
+            let mut input = inputs.next()?;
+            match self.read_input(&mut input, &read_options) {
+                Ok(Some(mut docs)) => {
+                    if let Some(head) = self.head {
+                        docs.jsons.truncate(head - count);
+                    }
+                    count += docs.jsons.len();
+                    return Some(Ok(docs));
+                }
+                Ok(None) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        });
+        let buffered = self.tail.is_some()
+            || self.random_sample.is_some()
+            || self.sort_by.is_some()
+            || self.count_by.is_some()
+            || self.concat_raw
+            || self.shuffle;
+        if !buffered {
+            return Ok(Box::new(streamed));
+        }
+        let documents: Documents = if self.tail.is_some() {
+            let mut documents = streamed.collect::<anyhow::Result<Vec<_>>>()?;
+            limit_documents(&mut documents, None, self.tail);
+            Box::new(documents.into_iter().map(Ok))
         } else {
-            Box::new(std::io::stdout().lock())
+            Box::new(streamed)
         };
-        match executor.execute(&docs.jsons, output_format, args.pretty, &mut writer) {
-            Ok(()) => {}
-            Err(err) => anyhow::bail!("{}", err),
+        let mut documents = match self.random_sample {
+            Some(n) => sample_documents(documents, n, &mut self.rng())?,
+            None => documents.collect::<anyhow::Result<Vec<_>>>()?,
+        };
+        if let Some(path) = &self.sort_by {
+            documents = concat_documents(documents);
+            for docs in &mut documents {
+                sort_documents(&mut docs.jsons, path, self.reverse)?;
+            }
+        }
+        if let Some(path) = &self.count_by {
+            documents = concat_documents(documents);
+            for docs in &mut documents {
+                docs.jsons = vec![count_documents(&docs.jsons, path)?];
+            }
+        }
+        // the outputs of a single execution are shuffled together
+        if self.concat_raw || self.shuffle {
+            documents = concat_documents(documents);
+        }
+        Ok(Box::new(documents.into_iter().map(Ok)))
+    }
+
+    /// Reads the documents of a single input and applies the options, which
+    /// select documents per input. Returns None for inputs skipped by --only.
+    fn read_input(
+        &self,
+        input: &mut Input,
+        read_options: &ReadOptions,
+    ) -> anyhow::Result<Option<JsonDocuments>> {
+        let mut docs = input
+            .read_to_docs(read_options)
+            .map_err(|err| match input.name.take() {
+                Some(file) => anyhow::Error::new(FileError { file, source: err }),
+                None => err,
+            })?;
+        if self.only.is_some_and(|only| docs.input_format != only) {
+            return Ok(None);
+        }
+        if !self.required_keys.is_empty() {
+            self.check_required_keys(&docs.jsons, input.name.as_deref())?;
+        }
+        if self.explode {
+            docs.jsons = explode(docs.jsons)?;
+        }
+        if self.explode_keys {
+            docs.jsons = explode_keys(docs.jsons)?;
+        }
+        if let Some(json_type) = self.type_filter {
+            docs.jsons.retain(|json| json_type.matches(json));
+        }
+        Ok(Some(docs))
+    }
+
+    /// Checks that every document contains all paths of --required-keys.
+    fn check_required_keys(&self, jsons: &[String], file: Option<&str>) -> anyhow::Result<()> {
+        for (idx, json) in jsons.iter().enumerate() {
+            let value: serde_json::Value = serde_json::from_str(json)?;
+            let missing: Vec<&str> = self
+                .required_keys
+                .iter()
+                .filter(|path| paths::lookup(&value, path).is_none())
+                .map(String::as_str)
+                .collect();
+            if missing.is_empty() {
+                continue;
+            }
+            let message = format!(
+                "document {} is missing required keys: {}",
+                idx + 1,
+                missing.join(", ")
+            );
+            if self.collect_errors {
+                self.log_format.log("warning", &message, file);
+                continue;
+            }
+            let source = anyhow::anyhow!(message);
+            return Err(match file {
+                Some(file) => anyhow::Error::new(FileError {
+                    file: file.to_owned(),
+                    source,
+                }),
+                None => source,
+            });
+        }
+        Ok(())
+    }
+
+    fn read_options(&self) -> ReadOptions {
+        ReadOptions {
+            explain_guess: self.explain_guess,
+            use_modeline: self.use_modeline,
+            input_limit: self.input_limit,
+            strict_json: self.strict_json,
+            strip_ansi: self.strip_ansi,
+            base64_decode: self.base64_decode,
+            raw_input: self.raw_input,
+        }
+    }
+
+    fn write_options(&self) -> WriteOptions {
+        WriteOptions {
+            pretty: self.pretty && !self.compact,
+            indent: self.indent,
+            tab: self.tab,
+            pretty_threshold: self.pretty_threshold.filter(|_| !self.compact),
+            toml_array: self.toml_array.clone(),
+            ron_array: self.ron_array,
+            toml_style: self.toml_style(),
+            plist_binary: self.plist_binary,
+            yaml_quote: if self.k8s {
+                yaml::QuoteStyle::Plain
+            } else {
+                self.yaml_quote
+            },
+            yaml_explicit_start: self.k8s,
+            yaml_anchors: self.yaml_anchors,
+            yaml_align: self.yaml_align,
+            prefix: self.prefix.clone().unwrap_or_default(),
+            suffix: self.suffix.clone().unwrap_or_default(),
+            env_export: self.env_export,
+            env_sort: self.env_sort,
+            sort_keys: if self.natural_sort {
+                Some(sort::KeyOrder::Natural)
+            } else {
+                (self.k8s || self.sort_keys || self.no_preserve_order)
+                    .then_some(sort::KeyOrder::Lexicographic)
+            },
+            output_separator: self.output_separator.clone(),
+            dedup: if self.dedup_global {
+                Some(Dedup::Global)
+            } else {
+                self.dedup.then_some(Dedup::Consecutive)
+            },
+            nth: self.nth,
+            keys_only: self.keys_only,
+            flatten: self.flatten.then(|| self.flatten_style()),
+            unflatten: self.unflatten.then(|| self.flatten_style()),
+            no_control_chars: self.no_control_chars,
+            validate_output: self.validate_output,
+            humanize_numbers: self.humanize_numbers,
+            numeric_output: self.numeric_output,
+            wrap: self.wrap,
+            raw0: self.raw0,
+        }
+    }
+
+    /// Reports an error returned by [`run`] on stderr in the configured
+    /// log format.
+    pub fn report(&self, err: &anyhow::Error) {
+        match self.log_format {
+            // mirrors the output of returning the error from main
+            LogFormat::Human => eprintln!("Error: {err:?}"),
+            LogFormat::Json => match err.downcast_ref::<FileError>() {
+                Some(file_err) => self.log_format.log(
+                    "error",
+                    &format!("{:#}", file_err.source),
+                    Some(&file_err.file),
+                ),
+                None => self.log_format.log("error", &format!("{err:#}"), None),
+            },
+        }
+    }
+
+    fn should_color(&self, format: Option<FileFormat>) -> bool {
+        let terminal = self.output.is_none() && std::io::stdout().is_terminal();
+        self.color_decision(format, std::env::var_os("NO_COLOR").is_some(), terminal)
+    }
+
+    /// Decides about colors given whether `NO_COLOR` is set and whether the
+    /// output goes to a terminal.
+    fn color_decision(&self, format: Option<FileFormat>, no_color: bool, terminal: bool) -> bool {
+        if self.plist_binary && format == Some(FileFormat::Plist) {
+            return false;
+        }
+        // encoded output has no syntax to highlight
+        if self.base64_encode {
+            return false;
+        }
+        // binary output must not be highlighted
+        if format == Some(FileFormat::MessagePack) {
+            return false;
+        }
+        match self.color {
+            Some(should) => should,
+            // see https://no-color.org
+            None if no_color => false,
+            None => terminal && format.is_some(),
         }
-        writer.flush()?;
     }
-    Ok(())
 }
 
-fn slurp(inputs: &mut [Input]) -> anyhow::Result<String> {
-    let mut jsons = Vec::<String>::new();
-    for input in inputs {
-        jsons.extend(input.read_to_docs()?.jsons.into_iter());
+/// Turns a jq string output into a raw string
+/// reverting quoting and escaping.
+fn pop_quotes(text: &str) -> String {
+    // check if the text starts with a quote
+    // if not its likely not a string returned
+    // by jq, so pass it through without scanning.
+    if !text.starts_with('"') {
+        return text.to_owned();
     }
-    let result = format!("[{}]", jsons.join(","));
-    anyhow::Ok(result)
+    match serde_json::from_str::<String>(text) {
+        Ok(mut decoded) => {
+            decoded.push('\n');
+            decoded
+        }
+        Err(_) => text.to_owned(),
+    }
+}
+
+/// Fails if a string or key of the value contains a control character
+/// other than tab, line feed or carriage return.
+fn reject_control_chars(value: &serde_json::Value) -> anyhow::Result<()> {
+    let check = |string: &str| match string
+        .chars()
+        .find(|char| char.is_control() && !matches!(char, '\t' | '\n' | '\r'))
+    {
+        Some(char) => anyhow::bail!(
+            "output contains control character {:?} in {:?}",
+            char,
+            string
+        ),
+        None => Ok(()),
+    };
+    match value {
+        serde_json::Value::String(string) => check(string),
+        serde_json::Value::Array(array) => array.iter().try_for_each(reject_control_chars),
+        serde_json::Value::Object(map) => map.iter().try_for_each(|(key, value)| {
+            check(key)?;
+            reject_control_chars(value)
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Replaces all strings, which are valid json numbers, with the number.
+/// Strings, which the number would not print back as, e.g. large integers
+/// rounded to a float, are kept, so no digits are lost.
+fn parse_numbers(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(string) => {
+            if let Ok(number) = serde_json::from_str::<serde_json::Number>(string) {
+                if number.to_string() == *string {
+                    *value = serde_json::Value::Number(number);
+                }
+            }
+        }
+        serde_json::Value::Array(array) => array.iter_mut().for_each(parse_numbers),
+        serde_json::Value::Object(map) => map.values_mut().for_each(parse_numbers),
+        _ => {}
+    }
+}
+
+/// Groups the digits of an integer output by thousands, e.g. 1,000,000.
+/// Any other output, including json strings, is returned unchanged.
+fn humanize_number(output: &str) -> String {
+    let text = output.trim_end_matches('\n');
+    let integer = text.parse::<i128>().map(|integer| integer.to_string());
+    if integer.as_deref() != Ok(text) {
+        return output.to_owned();
+    }
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", text),
+    };
+    let mut grouped = String::with_capacity(output.len() + digits.len() / 3);
+    grouped.push_str(sign);
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped.push_str(&output[text.len()..]);
+    grouped
+}
+
+/// Wraps every line of a raw output at width columns. Lines are broken at
+/// spaces where possible and words longer than width are split.
+fn wrap(output: &str, width: usize) -> String {
+    let text = output.trim_end_matches('\n');
+    let mut wrapped = String::with_capacity(output.len());
+    for (idx, line) in text.split('\n').enumerate() {
+        if idx > 0 {
+            wrapped.push('\n');
+        }
+        let mut column = 0;
+        for (idx, word) in line.split(' ').enumerate() {
+            if idx > 0 && column + 1 + word.chars().count() > width {
+                wrapped.push('\n');
+                column = 0;
+            } else if idx > 0 {
+                wrapped.push(' ');
+                column += 1;
+            }
+            for char in word.chars() {
+                if column == width {
+                    wrapped.push('\n');
+                    column = 0;
+                }
+                wrapped.push(char);
+                column += 1;
+            }
+        }
+    }
+    wrapped.push_str(&output[text.len()..]);
+    wrapped
 }
 
-#[cfg(test)]
-mod test {
-    use std::{error::Error, io::Cursor};
+/// Splits the newline separated results of a jq program,
+/// keeping their original formatting.
+fn split_results(output: &str) -> anyhow::Result<Vec<String>> {
+    let mut results = Vec::<String>::new();
+    let mut stream =
+        serde_json::Deserializer::from_str(output).into_iter::<serde::de::IgnoredAny>();
+    let mut start = 0;
+    while let Some(result) = stream.next() {
+        result?;
+        let end = stream.byte_offset();
+        results.push(format!("{}\n", output[start..end].trim()));
+        start = end;
+    }
+    Ok(results)
+}
+
+/// Writes raw outputs wrapped in prefix and suffix and joined by the
+/// separator. With raw0 every output is terminated by a NUL byte instead.
+fn write_raw<W: Write>(
+    outputs: &[String],
+    options: &WriteOptions,
+    writer: &mut W,
+) -> anyhow::Result<()> {
+    if options.raw0 {
+        for output in outputs {
+            // the newline appended by pop_quotes is replaced
+            let text = output.strip_suffix('\n').unwrap_or(output);
+            write!(writer, "{}{}{}\0", options.prefix, text, options.suffix)?;
+        }
+        return Ok(());
+    }
+    for (idx, output) in outputs.iter().enumerate() {
+        if idx > 0 {
+            writer.write_all(options.separator().as_bytes())?;
+        }
+        let text = output.strip_suffix('\n').unwrap_or(output);
+        write!(writer, "{}{}{}", options.prefix, text, options.suffix)?;
+    }
+    if !outputs.is_empty() {
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// The output format is the explicit one, the one of the output file's
+/// extension or else the input format.
+fn resolve_output_format(
+    explicit: Option<FileFormat>,
+    output: Option<&Path>,
+    input: FileFormat,
+) -> FileFormat {
+    let inferred = || {
+        let ext = ext_from_path(output?).ok()?;
+        FileFormat::from_extension(&ext).ok()
+    };
+    explicit.or_else(inferred).unwrap_or(input)
+}
+
+/// Format of diagnostics written to stderr.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl LogFormat {
+    /// Writes a diagnostic line to stderr.
+    fn log(self, level: &str, message: &str, file: Option<&str>) {
+        match self {
+            LogFormat::Human => match file {
+                Some(file) => eprintln!("{file}: {message}"),
+                None => eprintln!("{message}"),
+            },
+            LogFormat::Json => eprintln!(
+                "{}",
+                serde_json::json!({"level": level, "message": message, "file": file})
+            ),
+        }
+    }
+}
+
+/// Failure of a single input file, which keeps the file apart from the
+/// message for structured logs.
+#[derive(Debug)]
+struct FileError {
+    file: String,
+    source: anyhow::Error,
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {:#}", self.file, self.source)
+    }
+}
+
+impl std::error::Error for FileError {}
+
+/// Top level type of a json document.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum JsonType {
+    Object,
+    Array,
+    String,
+    Number,
+    Boolean,
+    Null,
+}
+
+impl JsonType {
+    fn matches(self, json: &str) -> bool {
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+            return false;
+        };
+        let actual = match value {
+            serde_json::Value::Object(_) => JsonType::Object,
+            serde_json::Value::Array(_) => JsonType::Array,
+            serde_json::Value::String(_) => JsonType::String,
+            serde_json::Value::Number(_) => JsonType::Number,
+            serde_json::Value::Bool(_) => JsonType::Boolean,
+            serde_json::Value::Null => JsonType::Null,
+        };
+        actual == self
+    }
+}
+
+/// Which duplicate outputs are suppressed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Dedup {
+    Consecutive,
+    Global,
+}
+
+/// A compiled jq program, which runs either in place or on a worker
+/// thread when a timeout is given.
+enum Program {
+    Local(jq_rs::JqProgram),
+    Worker(worker::Worker),
+}
+
+impl Program {
+    fn run(&mut self, json: &str) -> anyhow::Result<String> {
+        match self {
+            Program::Local(program) => program.run(json).map_err(|err| anyhow::anyhow!("{}", err)),
+            Program::Worker(worker) => worker.run(json),
+        }
+    }
+}
+
+struct Executor {
+    program: Program,
+    // outputs already written, kept across inputs for deduplication
+    seen: std::collections::HashSet<String>,
+    last: Option<String>,
+    // failures of single documents are collected instead of aborting
+    collect_errors: bool,
+    errors: Vec<String>,
+    // documents run so far across all inputs
+    count: usize,
+    // outputs are randomly reordered, when set
+    shuffle: Option<sample::Rng>,
+}
+
+impl Executor {
+    fn new(program: &str) -> anyhow::Result<Executor> {
+        let program = jq_rs::compile(program).map_err(|err| anyhow::anyhow!("{}", err))?;
+        Ok(Self::with_program(Program::Local(program)))
+    }
+
+    fn with_timeout(program: &str, timeout: Duration) -> anyhow::Result<Executor> {
+        let worker = worker::Worker::spawn(program, timeout)?;
+        Ok(Self::with_program(Program::Worker(worker)))
+    }
+
+    fn with_program(program: Program) -> Executor {
+        Self {
+            program,
+            seen: std::collections::HashSet::new(),
+            last: None,
+            collect_errors: false,
+            errors: Vec::new(),
+            count: 0,
+            shuffle: None,
+        }
+    }
+
+    fn dedup(&mut self, outputs: &mut Vec<String>, dedup: Dedup) {
+        outputs.retain(|output| match dedup {
+            Dedup::Consecutive => {
+                let duplicate = self.last.as_ref() == Some(output);
+                self.last = Some(output.clone());
+                !duplicate
+            }
+            Dedup::Global => self.seen.insert(output.clone()),
+        });
+    }
+
+    /// Logs the failures collected with --collect-errors and fails, if
+    /// there are any.
+    fn report_errors(&self, log_format: LogFormat) -> anyhow::Result<()> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        for error in &self.errors {
+            log_format.log("error", error, None);
+        }
+        anyhow::bail!(
+            "failed to execute jq program on {} document(s)",
+            self.errors.len()
+        );
+    }
+
+    /// Runs the program on every document and returns each result
+    /// of the program as a separate output.
+    fn run(&mut self, jsons: &[String]) -> anyhow::Result<Vec<String>> {
+        let mut outputs = Vec::<String>::new();
+        for json in jsons {
+            self.count += 1;
+            let output = match self.program.run(json) {
+                Ok(output) => output,
+                Err(err) if self.collect_errors => {
+                    self.errors
+                        .push(format!("document {}: {}", self.count, err));
+                    continue;
+                }
+                Err(err) => anyhow::bail!("failed to execute jq program: {}", err),
+            };
+            outputs.extend(split_results(&output)?);
+        }
+        Ok(outputs)
+    }
+
+    fn execute<W: Write>(
+        &mut self,
+        jsons: &[String],
+        output_format: Option<FileFormat>,
+        options: &WriteOptions,
+        writer: &mut W,
+    ) -> anyhow::Result<()> {
+        let mut outputs = self.run(jsons)?;
+        if let Some(nth) = options.nth {
+            outputs = outputs.into_iter().nth(nth).into_iter().collect();
+        }
+        if options.keys_only {
+            for output in &mut outputs {
+                let serde_json::Value::Object(map) = serde_json::from_str(output)? else {
+                    anyhow::bail!(
+                        "--keys-only requires object documents, got {}",
+                        output.trim_end()
+                    );
+                };
+                let keys: Vec<&String> = map.keys().collect();
+                *output = format!("{}\n", serde_json::to_string(&keys)?);
+            }
+        }
+        if let Some(style) = &options.flatten {
+            for output in &mut outputs {
+                let value = serde_json::from_str(output)?;
+                *output = format!("{}\n", flatten::flatten(&value, style));
+            }
+        }
+        if let Some(style) = &options.unflatten {
+            for output in &mut outputs {
+                let value = serde_json::from_str(output)?;
+                *output = format!("{}\n", flatten::unflatten(&value, style));
+            }
+        }
+        if options.numeric_output {
+            for output in &mut outputs {
+                let mut value = serde_json::from_str(output)?;
+                parse_numbers(&mut value);
+                *output = format!("{value}\n");
+            }
+        }
+        if let Some(order) = options.sort_keys {
+            for output in &mut outputs {
+                let mut value = serde_json::from_str(output)?;
+                sort::sort_keys(&mut value, order);
+                *output = format!("{value}\n");
+            }
+        }
+        if options.no_control_chars {
+            for output in &outputs {
+                reject_control_chars(&serde_json::from_str(output)?)?;
+            }
+        }
+        if let Some(dedup) = options.dedup {
+            self.dedup(&mut outputs, dedup);
+        }
+        if let Some(rng) = &mut self.shuffle {
+            sample::shuffle(&mut outputs, rng);
+        }
+        if output_format.is_none() {
+            // numbers are humanized before the quotes of strings are removed,
+            // so numeric strings are left alone
+            if options.humanize_numbers {
+                outputs = outputs
+                    .iter()
+                    .map(|output| humanize_number(output))
+                    .collect();
+            }
+            outputs = outputs.iter().map(|output| pop_quotes(output)).collect();
+            if let Some(width) = options.wrap {
+                outputs = outputs.iter().map(|output| wrap(output, width)).collect();
+            }
+        }
+        match output_format {
+            Some(format) if options.validate_output => {
+                let mut serialized = Vec::<u8>::new();
+                format
+                    .write_format(&outputs, options, &mut serialized)
+                    .map_err(|err| anyhow::anyhow!("failed to produce output: {}", err))?;
+                format.validate_output(&serialized)?;
+                writer.write_all(&serialized)?;
+            }
+            Some(format) => format
+                .write_format(&outputs, options, writer)
+                .map_err(|err| anyhow::anyhow!("failed to produce output: {}", err))?,
+            None => write_raw(&outputs, options, writer)?,
+        }
+        anyhow::Ok(())
+    }
+}
+
+/// Runs nuq
+/// # Errors
+/// When arg parsing, io, ... fails
+/// # Panics
+/// When the executor is somehow not initialized.
+pub fn run(args: &Args) -> anyhow::Result<()> {
+    args.validate()?;
+    if let Some(program) = args.program.as_deref().filter(|_| args.program_is_file()) {
+        args.log_format.log(
+            "warning",
+            &format!("reading {program} as input, the program defaults to ."),
+            None,
+        );
+    }
+    let program = args.jq_program()?;
+    let mut executor = match args.timeout {
+        Some(timeout) => Executor::with_timeout(&program, timeout)?,
+        None => Executor::new(&program)?,
+    };
+    executor.collect_errors = args.collect_errors;
+    executor.shuffle = args.shuffle.then(|| args.rng());
+    let options = args.write_options();
+    // the program is compiled once and the highlight styles are only loaded
+    // once when needed, so both are shared across all inputs
+    let styles = std::cell::OnceCell::<highlight::Styles>::new();
+    // a broken theme file fails before any output is written
+    let theme_file = args
+        .theme_file
+        .as_deref()
+        .map(highlight::load_theme)
+        .transpose()?;
+    let mut tee = args.tee.as_ref().map(File::create).transpose()?;
+    let documents = args.read_documents()?;
+    // a dry run renders into memory to compare it with the output file
+    let mut planned = Vec::<u8>::new();
+    let mut sink = if args.dry_run {
+        Box::new(&mut planned)
+    } else {
+        output_writer(args)?
+    };
+    // base64 output is encoded at once after everything is rendered
+    let mut unencoded = Vec::<u8>::new();
+    let stdout: &mut dyn Write = if args.base64_encode {
+        &mut unencoded
+    } else {
+        &mut sink
+    };
+    // documents split off a larger input are separated like in a stream
+    let split_options = WriteOptions {
+        yaml_explicit_start: true,
+        ..options.clone()
+    };
+    let mut documents = documents
+        .flat_map(|docs| args.split_for_flushing(docs))
+        .peekable();
+    while let Some(docs) = documents.next() {
+        let (docs, split) = docs?;
+        if args.validate_only {
+            executor.run(&docs.jsons)?;
+            continue;
+        }
+        let output_format = args.output_format_of(docs.input_format);
+        let options = if split { &split_options } else { &options };
+        let diff_mode = args.diff || args.color_diff;
+        // render without colors first, so the tee file receives plain output
+        let (mut rendered, mut colored) =
+            render(args, &mut executor, &docs, output_format, options)?;
+        if args.no_trailing_newline && documents.peek().is_none() {
+            for text in std::iter::once(&mut rendered).chain(colored.as_mut()) {
+                if text.last() == Some(&b'\n') {
+                    text.pop();
+                }
+            }
+        }
+        if let Some(tee) = &mut tee {
+            tee.write_all(&rendered)?;
+        }
+        // raw output is highlighted as json, if it happens to be json
+        let highlight_format =
+            output_format.or_else(|| is_json(&rendered).then_some(FileFormat::Json));
+        let highlighted =
+            !diff_mode && highlight_format.is_some() && args.should_color(output_format);
+        let mut writer: Box<dyn Write> = match highlight_format {
+            Some(format) if highlighted => {
+                let styles = styles.get_or_init(highlight::Styles::default);
+                Box::new(highlight_writer(
+                    args,
+                    &mut *stdout,
+                    format,
+                    styles,
+                    theme_file.as_ref(),
+                )?)
+            }
+            _ => Box::new(&mut *stdout),
+        };
+        writer.write_all(colored.as_ref().unwrap_or(&rendered))?;
+        // the highlighting writer only writes on flush
+        if highlighted || args.unbuffered {
+            writer.flush()?;
+        }
+    }
+    stdout.flush()?;
+    if args.base64_encode {
+        writeln!(
+            sink,
+            "{}",
+            base64::engine::general_purpose::STANDARD.encode(&unencoded)
+        )?;
+        sink.flush()?;
+    }
+    drop(sink);
+    if let Some(path) = args.output.as_ref().filter(|_| args.dry_run) {
+        report_dry_run(path, &planned)?;
+    }
+    executor.report_errors(args.log_format)
+}
+
+/// Creates the writer highlighting with the theme file or the named theme.
+fn highlight_writer<'a, W: Write>(
+    args: &Args,
+    wrapped: W,
+    format: FileFormat,
+    styles: &'a highlight::Styles,
+    theme_file: Option<&'a syntect::highlighting::Theme>,
+) -> anyhow::Result<highlight::Writer<'a, W>> {
+    let writer = match theme_file {
+        Some(theme) => highlight::Writer::with_theme(wrapped, format, styles, theme),
+        None => highlight::Writer::new(
+            wrapped,
+            format,
+            styles,
+            args.theme.as_deref().unwrap_or(highlight::DEFAULT_THEME),
+        )?,
+    };
+    Ok(writer.with_depth(args.color_depth))
+}
+
+/// The destination of the output: stdout, the output file or nothing.
+fn output_writer(args: &Args) -> anyhow::Result<Box<dyn Write>> {
+    Ok(match &args.output {
+        _ if args.validate_only => Box::new(std::io::sink()),
+        Some(path) => Box::new(std::io::BufWriter::new(File::create(path).map_err(
+            |err| anyhow::anyhow!("failed to create {}: {}", path.display(), err),
+        )?)),
+        None if args.quiet => Box::new(std::io::sink()),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+    })
+}
+
+/// Prints whether writing the planned content would change the file.
+fn report_dry_run(path: &Path, planned: &[u8]) -> anyhow::Result<()> {
+    let status = match std::fs::read(path) {
+        Ok(existing) if existing == planned => "unchanged",
+        Ok(_) => "would change",
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => "would be created",
+        Err(err) => anyhow::bail!("failed to read {}: {}", path.display(), err),
+    };
+    println!("{}: {}", path.display(), status);
+    Ok(())
+}
+
+/// Whether text is a non-empty stream of json values.
+fn is_json(text: &[u8]) -> bool {
+    let mut values =
+        serde_json::Deserializer::from_slice(text).into_iter::<serde::de::IgnoredAny>();
+    values.next().is_some_and(|first| first.is_ok()) && values.all(|value| value.is_ok())
+}
+
+/// Renders the output for the documents of a single input. Returns the
+/// plain and, for a colored diff, the colored output.
+fn render(
+    args: &Args,
+    executor: &mut Executor,
+    docs: &JsonDocuments,
+    output_format: Option<FileFormat>,
+    options: &WriteOptions,
+) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+    let diff_mode = args.diff || args.color_diff;
+    let mut rendered = Vec::<u8>::new();
+    let mut colored = None;
+    if args.describe {
+        for json in &docs.jsons {
+            let value = serde_json::from_str(json)?;
+            let format = docs.input_format.to_extension();
+            writeln!(rendered, "{}", describe::describe(&value, format))?;
+        }
+    } else if args.paths {
+        let outputs = executor.run(&docs.jsons)?;
+        paths::write_paths(&outputs, &mut rendered)?;
+    } else if args.to_csv {
+        let mut values = Vec::new();
+        for output in executor.run(&docs.jsons)? {
+            values.push(serde_json::from_str(&output)?);
+        }
+        let flatten_style = args.flatten_style();
+        let rows = tabular::rows(values)?
+            .into_iter()
+            .map(|row| flatten::flatten_to_map(&serde_json::Value::Object(row), &flatten_style))
+            .collect::<Vec<_>>();
+        tabular::write_rows(&rows, b',', &mut rendered)?;
+    } else if diff_mode {
+        let color = args.color_diff && args.should_color(output_format);
+        let format = output_format.unwrap_or(docs.input_format);
+        let context = args.context_lines;
+        (rendered, colored) = render_diff(executor, docs, format, options, context, color)?;
+    } else {
+        match executor.execute(&docs.jsons, output_format, options, &mut rendered) {
+            Ok(()) => {}
+            Err(err) => anyhow::bail!("{}", err),
+        }
+    }
+    Ok((rendered, colored))
+}
+
+/// Renders the diff between the input documents and the program output,
+/// both in the output format. Returns the plain and, if requested, the
+/// colored diff.
+fn render_diff(
+    executor: &mut Executor,
+    docs: &JsonDocuments,
+    format: FileFormat,
+    options: &WriteOptions,
+    context: usize,
+    color: bool,
+) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+    let mut before = Vec::<u8>::new();
+    format.write_format(&docs.jsons, options, &mut before)?;
+    let mut after = Vec::<u8>::new();
+    executor.execute(&docs.jsons, Some(format), options, &mut after)?;
+    let before = String::from_utf8(before)?;
+    let after = String::from_utf8(after)?;
+    let mut plain = Vec::<u8>::new();
+    diff::write_unified(&before, &after, context, false, &mut plain)?;
+    if !color {
+        return Ok((plain, None));
+    }
+    let mut colored = Vec::<u8>::new();
+    diff::write_unified(&before, &after, context, true, &mut colored)?;
+    Ok((plain, Some(colored)))
+}
+
+/// The documents of every input in the order of the inputs.
+type Documents<'a> = Box<dyn Iterator<Item = anyhow::Result<JsonDocuments>> + 'a>;
+
+/// Keeps only the first head documents and of those only the last tail
+/// documents across all inputs.
+fn limit_documents(documents: &mut [JsonDocuments], head: Option<usize>, tail: Option<usize>) {
+    let total: usize = documents.iter().map(|docs| docs.jsons.len()).sum();
+    let end = head.map_or(total, |head| head.min(total));
+    let start = tail.map_or(0, |tail| end.saturating_sub(tail));
+    let mut offset = 0;
+    for docs in documents {
+        let len = docs.jsons.len();
+        let keep_start = start.saturating_sub(offset).min(len);
+        let keep_end = end.saturating_sub(offset).min(len);
+        docs.jsons.truncate(keep_end);
+        docs.jsons.drain(..keep_start);
+        offset += len;
+    }
+}
+
+/// Splits content on lines consisting of ===. Blank chunks are dropped.
+fn split_chunks(content: &str) -> Vec<String> {
+    let mut chunks = vec![String::new()];
+    for line in content.split_inclusive('\n') {
+        if line.trim_end() == "===" {
+            chunks.push(String::new());
+        } else if let Some(chunk) = chunks.last_mut() {
+            chunk.push_str(line);
+        }
+    }
+    chunks.retain(|chunk| !chunk.trim().is_empty());
+    chunks
+}
+
+/// Replaces every array document with its elements.
+fn explode(jsons: Vec<String>) -> anyhow::Result<Vec<String>> {
+    let mut exploded = Vec::with_capacity(jsons.len());
+    for json in jsons {
+        match serde_json::from_str(&json)? {
+            serde_json::Value::Array(array) => {
+                exploded.extend(array.iter().map(serde_json::Value::to_string));
+            }
+            _ => exploded.push(json),
+        }
+    }
+    Ok(exploded)
+}
+
+/// Splits object documents into a document per entry.
+fn explode_keys(jsons: Vec<String>) -> anyhow::Result<Vec<String>> {
+    let mut exploded = Vec::with_capacity(jsons.len());
+    for json in jsons {
+        match serde_json::from_str(&json)? {
+            serde_json::Value::Object(map) => {
+                exploded.extend(map.into_iter().map(|entry| {
+                    serde_json::Value::Object(serde_json::Map::from_iter([entry])).to_string()
+                }));
+            }
+            _ => exploded.push(json),
+        }
+    }
+    Ok(exploded)
+}
+
+/// Combines the documents of all inputs into a single one.
+fn concat_documents(documents: Vec<JsonDocuments>) -> Vec<JsonDocuments> {
+    let mut documents = documents.into_iter();
+    let Some(mut combined) = documents.next() else {
+        return Vec::new();
+    };
+    for docs in documents {
+        combined.jsons.extend(docs.jsons);
+    }
+    vec![combined]
+}
+
+/// The first result of the jq path on every document. Documents without
+/// a result yield null.
+fn path_values(jsons: &[String], path: &str) -> anyhow::Result<Vec<serde_json::Value>> {
+    let mut executor = Executor::new(path)?;
+    let mut values = Vec::with_capacity(jsons.len());
+    for json in jsons {
+        values.push(match executor.run(std::slice::from_ref(json))?.first() {
+            Some(value) => serde_json::from_str(value)?,
+            None => serde_json::Value::Null,
+        });
+    }
+    Ok(values)
+}
+
+/// Sorts documents stably by the result of the jq path on them in jq
+/// order. Documents without a result are sorted like null.
+fn sort_documents(jsons: &mut Vec<String>, path: &str, reverse: bool) -> anyhow::Result<()> {
+    let keys = path_values(jsons, path)?;
+    let mut keyed: Vec<_> = keys.into_iter().zip(jsons.drain(..)).collect();
+    keyed.sort_by(|(a, _), (b, _)| {
+        let ordering = sort::compare_values(a, b);
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    jsons.extend(keyed.into_iter().map(|(_, json)| json));
+    Ok(())
+}
+
+/// Counts the documents per distinct result of the jq path in order of
+/// first occurrence. Strings are used as keys directly, other values by
+/// their json text.
+fn count_documents(jsons: &[String], path: &str) -> anyhow::Result<String> {
+    let mut counts = serde_json::Map::new();
+    for value in path_values(jsons, path)? {
+        let key = match value {
+            serde_json::Value::String(key) => key,
+            other => other.to_string(),
+        };
+        let count = counts.entry(key).or_insert(serde_json::Value::from(0));
+        *count = serde_json::Value::from(count.as_u64().unwrap_or_default() + 1);
+    }
+    Ok(serde_json::Value::Object(counts).to_string())
+}
+
+/// Randomly keeps up to n documents across all inputs in a single pass,
+/// so only the sampled documents are held in memory.
+fn sample_documents(
+    documents: Documents,
+    n: usize,
+    rng: &mut sample::Rng,
+) -> anyhow::Result<Vec<JsonDocuments>> {
+    let mut reservoir = sample::Reservoir::new(n);
+    let mut formats = Vec::new();
+    for (input, docs) in documents.enumerate() {
+        let docs = docs?;
+        formats.push(docs.input_format);
+        for json in docs.jsons {
+            reservoir.offer((input, json), rng);
+        }
+    }
+    let mut sampled: Vec<_> = formats
+        .into_iter()
+        .map(|format| JsonDocuments::new(Vec::new(), format))
+        .collect();
+    for (input, json) in reservoir.into_items() {
+        sampled[input].jsons.push(json);
+    }
+    Ok(sampled)
+}
+
+fn slurp(inputs: &mut [Input], options: &ReadOptions) -> anyhow::Result<String> {
+    let mut jsons = Vec::<String>::new();
+    for input in inputs {
+        jsons.extend(input.read_to_docs(options)?.jsons);
+    }
+    let result = format!("[{}]", jsons.join(","));
+    anyhow::Ok(result)
+}
+
+fn merge(
+    inputs: &mut [Input],
+    options: &ReadOptions,
+    arrays: merge::ArrayMerge,
+) -> anyhow::Result<String> {
+    let mut merged = serde_json::Value::Null;
+    for input in inputs {
+        for json in input.read_to_docs(options)?.jsons {
+            merge::deep_merge(&mut merged, serde_json::from_str(&json)?, arrays);
+        }
+    }
+    anyhow::Ok(merged.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{error::Error, io::Cursor, time::Duration};
+
+    use crate::{Dedup, Executor, FileFormat, Input, JsonDocuments, ReadOptions, WriteOptions};
+
+    fn execute_str(
+        executor: &mut Executor,
+        value: &str,
+        input_format: FileFormat,
+        output_format: Option<FileFormat>,
+    ) -> Result<String, Box<dyn Error>> {
+        let jsons =
+            input_format.read_to_json(Cursor::new(value.as_bytes()), &ReadOptions::default())?;
+        let mut buf = Vec::<u8>::new();
+        executor.execute(
+            &jsons,
+            output_format,
+            &WriteOptions::default(),
+            &mut Cursor::new(&mut buf),
+        )?;
+        let result = String::from_utf8(buf)?;
+        Ok(result)
+    }
+
+    #[test]
+    fn file_format_from_extension() {
+        assert_eq!(
+            FileFormat::from_extension("json").unwrap(),
+            FileFormat::Json
+        );
+        assert_eq!(FileFormat::from_extension("ron").unwrap(), FileFormat::Ron);
+        assert_eq!(
+            FileFormat::from_extension("yaml").unwrap(),
+            FileFormat::Yaml
+        );
+        assert_eq!(FileFormat::from_extension("yml").unwrap(), FileFormat::Yaml);
+        assert_eq!(
+            FileFormat::from_extension("toml").unwrap(),
+            FileFormat::Toml
+        );
+        assert_eq!(
+            FileFormat::from_extension("jsonl").unwrap(),
+            FileFormat::Json
+        );
+        assert_eq!(
+            FileFormat::from_extension("mpk").unwrap(),
+            FileFormat::MessagePack
+        );
+        assert!(FileFormat::from_extension("garbage").is_err());
+    }
+
+    #[test]
+    fn file_format_aliases() {
+        use clap::ValueEnum;
+        assert_eq!(FileFormat::from_str("j", false).unwrap(), FileFormat::Json);
+        assert_eq!(FileFormat::from_str("y", false).unwrap(), FileFormat::Yaml);
+        assert_eq!(FileFormat::from_str("r", false).unwrap(), FileFormat::Ron);
+        assert_eq!(FileFormat::from_str("t", false).unwrap(), FileFormat::Toml);
+        assert_eq!(
+            FileFormat::from_str("yaml", false).unwrap(),
+            FileFormat::Yaml
+        );
+    }
+
+    #[test]
+    fn resolve_output_format() {
+        use std::path::Path;
+
+        use super::resolve_output_format;
+        let yaml = Some(Path::new("out/result.yaml"));
+        let json = FileFormat::Json;
+        assert_eq!(resolve_output_format(None, yaml, json), FileFormat::Yaml);
+        assert_eq!(
+            resolve_output_format(Some(FileFormat::Toml), yaml, json),
+            FileFormat::Toml
+        );
+        assert_eq!(resolve_output_format(None, None, json), json);
+        assert_eq!(
+            resolve_output_format(None, Some(Path::new("result.out")), json),
+            json
+        );
+    }
+
+    #[test]
+    fn ext_from_dotfile() {
+        assert_eq!(super::ext_from_path(".env").unwrap(), "env");
+        assert_eq!(super::ext_from_path("dir/.env").unwrap(), "env");
+        assert_eq!(super::ext_from_path("a.yaml").unwrap(), "yaml");
+        assert_eq!(super::ext_from_path(".config.yaml").unwrap(), "yaml");
+    }
+
+    #[test]
+    fn file_format_to_extension() {
+        assert_eq!(FileFormat::Json.to_extension(), "json");
+        assert_eq!(FileFormat::Yaml.to_extension(), "yaml");
+        assert_eq!(FileFormat::Toml.to_extension(), "toml");
+        assert_eq!(FileFormat::Ron.to_extension(), "ron");
+        assert_eq!(FileFormat::Plist.to_extension(), "plist");
+        assert_eq!(FileFormat::Env.to_extension(), "env");
+        assert_eq!(FileFormat::Csv.to_extension(), "csv");
+        assert_eq!(FileFormat::Tsv.to_extension(), "tsv");
+        assert_eq!(FileFormat::Xml.to_extension(), "xml");
+        assert_eq!(FileFormat::Cson.to_extension(), "cson");
+        assert_eq!(FileFormat::Json5.to_extension(), "json5");
+        assert_eq!(FileFormat::MessagePack.to_extension(), "msgpack");
+    }
+
+    #[test]
+    fn identity_json() -> Result<(), Box<dyn Error>> {
+        let json = r#"{"a":"b"}"#;
+        let mut executor = Executor::new(".")?;
+        let result = execute_str(
+            &mut executor,
+            json,
+            FileFormat::Json,
+            Some(FileFormat::Json),
+        )?;
+        assert_eq!(result, format!("{json}\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn identity_csv() -> Result<(), Box<dyn Error>> {
+        let csv = "a,b\n1,\"x, y\"\n2,\n";
+        let mut executor = Executor::new(".")?;
+        let result = execute_str(&mut executor, csv, FileFormat::Csv, Some(FileFormat::Csv))?;
+        assert_eq!(result, csv);
+        Ok(())
+    }
+
+    #[test]
+    fn identity_tsv() -> Result<(), Box<dyn Error>> {
+        let tsv = "name\tcity\nsmith, j.\tberlin, de\n";
+        let mut executor = Executor::new(".")?;
+        let result = execute_str(&mut executor, tsv, FileFormat::Tsv, Some(FileFormat::Tsv))?;
+        assert_eq!(result, tsv);
+        let json = execute_str(&mut executor, tsv, FileFormat::Tsv, Some(FileFormat::Json))?;
+        assert_eq!(json, "[{\"name\":\"smith, j.\",\"city\":\"berlin, de\"}]\n");
+        Ok(())
+    }
+
+    #[test]
+    fn csv_nested_object() -> Result<(), Box<dyn Error>> {
+        let mut executor = Executor::new(".")?;
+        let json = r#"[{"a":{"b":1}}]"#;
+        let result = execute_str(&mut executor, json, FileFormat::Json, Some(FileFormat::Csv));
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn identity_yaml() -> Result<(), Box<dyn Error>> {
+        let yaml = "a: b";
+        let mut executor = Executor::new(".")?;
+        let result = execute_str(
+            &mut executor,
+            yaml,
+            FileFormat::Yaml,
+            Some(FileFormat::Yaml),
+        )?;
+        assert_eq!(result, format!("{yaml}\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn explicit_null_yaml() -> Result<(), Box<dyn Error>> {
+        let json = r#"{"a":null,"b":{"c":null}}"#;
+        let mut executor = Executor::new(".")?;
+        let yaml = execute_str(
+            &mut executor,
+            json,
+            FileFormat::Json,
+            Some(FileFormat::Yaml),
+        )?;
+        assert_eq!(yaml, "a: null\nb:\n  c: null\n");
+        let options = WriteOptions {
+            yaml_quote: crate::yaml::QuoteStyle::Single,
+            ..Default::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        FileFormat::Yaml.write_format(&[json.to_owned()], &options, &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, yaml);
+        let jsons = FileFormat::Yaml
+            .read_to_json(Cursor::new("a: ~\nb:\n  c:"), &ReadOptions::default())?;
+        assert_eq!(jsons, vec![json]);
+        Ok(())
+    }
+
+    #[test]
+    fn toml_literal_strings() -> Result<(), Box<dyn Error>> {
+        let mut executor = Executor::new(".")?;
+        let result = execute_str(
+            &mut executor,
+            r#"{"pattern":"\\d+\\.\\d+"}"#,
+            FileFormat::Json,
+            Some(FileFormat::Toml),
+        )?;
+        assert_eq!(result, "pattern = '\\d+\\.\\d+'\n");
+        Ok(())
+    }
+
+    #[test]
+    fn toml_null() {
+        let mut executor = Executor::new(".").unwrap();
+        let result = execute_str(
+            &mut executor,
+            r#"{"a":{"b":null}}"#,
+            FileFormat::Json,
+            Some(FileFormat::Toml),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("toml can not represent null, found at .a.b"));
+    }
+
+    #[test]
+    fn identity_multi_yaml() -> Result<(), Box<dyn Error>> {
+        let yaml = "a: b\n---\na: c";
+        let mut executor = Executor::new(".")?;
+        let result = execute_str(
+            &mut executor,
+            yaml,
+            FileFormat::Yaml,
+            Some(FileFormat::Yaml),
+        )?;
+        assert_eq!(&result, "---\na: b\n---\na: c\n");
+        Ok(())
+    }
+
+    #[test]
+    fn identity_ron() -> Result<(), Box<dyn Error>> {
+        let ron = r#"(a: "b")"#;
+        let mut executor = Executor::new(".")?;
+        let result = execute_str(&mut executor, ron, FileFormat::Ron, Some(FileFormat::Ron))?;
+        assert_eq!(result, "{\"a\":\"b\"}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn identity_toml() -> Result<(), Box<dyn Error>> {
+        let ron = r#"a = "b""#;
+        let mut executor = Executor::new(".")?;
+        let result = execute_str(&mut executor, ron, FileFormat::Toml, Some(FileFormat::Toml))?;
+        assert_eq!(result, "a = \"b\"\n");
+        Ok(())
+    }
+
+    #[test]
+    fn cargo_lock_round_trip() -> Result<(), Box<dyn Error>> {
+        let lock = r#"version = 3
+
+[[package]]
+name = "anyhow"
+version = "1.0.95"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "34ac096ce696dc2fcabef30516bb13c0a68a11d30131d3df6f04711467681b04"
+
+[[package]]
+name = "nuq"
+version = "0.1.4"
+dependencies = [
+    "anyhow",
+    "clap",
+]
+"#;
+        let jsons = FileFormat::Toml.read_to_json(Cursor::new(lock), &ReadOptions::default())?;
+        assert_eq!(jsons.len(), 1);
+        let value: serde_json::Value = serde_json::from_str(&jsons[0])?;
+        assert_eq!(value["package"].as_array().map(Vec::len), Some(2));
+        let options = WriteOptions {
+            pretty: true,
+            ..WriteOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        FileFormat::Toml.write_format(&jsons, &options, &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, lock);
+        Ok(())
+    }
+
+    #[test]
+    fn toml_scalars_before_tables() -> Result<(), Box<dyn Error>> {
+        let json = r#"{"package":[{"name":"a"}],"version":3}"#.to_owned();
+        let mut buf = Vec::<u8>::new();
+        FileFormat::Toml.write_format(&[json], &WriteOptions::default(), &mut buf)?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "version = 3\n\n[[package]]\nname = \"a\"\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn multi_yaml_to_toml_array() -> Result<(), Box<dyn Error>> {
+        let yaml = "a: 1\n---\na: 2\n---\na: 3";
+        let jsons = FileFormat::Yaml.read_to_json(Cursor::new(yaml), &ReadOptions::default())?;
+        let options = WriteOptions {
+            toml_array: Some("docs".to_owned()),
+            ..WriteOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        FileFormat::Toml.write_format(&jsons, &options, &mut buf)?;
+        let result = String::from_utf8(buf)?;
+        assert_eq!(
+            result,
+            "[[docs]]\na = 1\n\n[[docs]]\na = 2\n\n[[docs]]\na = 3\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ron_array() -> Result<(), Box<dyn Error>> {
+        let jsons = [r#"{"a":1}"#.to_owned(), r#"{"a":2}"#.to_owned()];
+        let mut buf = Vec::<u8>::new();
+        assert!(FileFormat::Ron
+            .write_format(&jsons, &WriteOptions::default(), &mut buf)
+            .is_err());
+        let options = WriteOptions {
+            ron_array: true,
+            ..WriteOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        FileFormat::Ron.write_format(&jsons, &options, &mut buf)?;
+        let ron = String::from_utf8(buf)?;
+        assert_eq!(ron, "[{\"a\":1},{\"a\":2}]\n");
+        let value: serde_json::Value = ron::from_str(&ron)?;
+        assert_eq!(value, serde_json::json!([{"a": 1}, {"a": 2}]));
+        let mut buf = Vec::<u8>::new();
+        FileFormat::Ron.write_format(&jsons[..1], &options, &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, "{\"a\":1}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn default_toml_array() -> Result<(), Box<dyn Error>> {
+        let jsons = [r#"{"a":1}"#.to_owned(), r#"{"a":2}"#.to_owned()];
+        let mut buf = Vec::<u8>::new();
+        FileFormat::Toml.write_format(&jsons, &WriteOptions::default(), &mut buf)?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "[[documents]]\na = 1\n\n[[documents]]\na = 2\n"
+        );
+        let mut buf = Vec::<u8>::new();
+        FileFormat::Toml.write_format(&jsons[..1], &WriteOptions::default(), &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, "a = 1\n");
+        Ok(())
+    }
+
+    #[test]
+    fn slurped_toml_array() -> Result<(), Box<dyn Error>> {
+        let options = WriteOptions {
+            toml_array: Some("docs".to_owned()),
+            ..WriteOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        FileFormat::Toml.write_format(&[r#"[{"a":1},{"a":2}]"#.to_owned()], &options, &mut buf)?;
+        let result = String::from_utf8(buf)?;
+        assert_eq!(result, "[[docs]]\na = 1\n\n[[docs]]\na = 2\n");
+        Ok(())
+    }
+
+    #[test]
+    fn read_plist() -> Result<(), Box<dyn Error>> {
+        let plist = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>name</key>
+    <string>nuq</string>
+    <key>count</key>
+    <integer>3</integer>
+    <key>created</key>
+    <date>2024-01-02T03:04:05Z</date>
+    <key>blob</key>
+    <data>aGVsbG8=</data>
+</dict>
+</plist>"#;
+        let jsons = FileFormat::Plist.read_to_json(Cursor::new(plist), &ReadOptions::default())?;
+        assert_eq!(
+            jsons,
+            vec![r#"{"name":"nuq","count":3,"created":"2024-01-02T03:04:05Z","blob":"aGVsbG8="}"#]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn json_output_separator() -> Result<(), Box<dyn Error>> {
+        let options = WriteOptions {
+            output_separator: Some(", ".to_owned()),
+            ..WriteOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        FileFormat::Json.write_format(&["1".to_owned(), "{}".to_owned()], &options, &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, "1, {}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn string_json() -> Result<(), Box<dyn Error>> {
+        let json = r#"{"a":"b"}"#;
+        let mut executor = Executor::new(".a")?;
+        let result = execute_str(
+            &mut executor,
+            json,
+            FileFormat::Json,
+            Some(FileFormat::Json),
+        )?;
+        assert_eq!(result, "\"b\"\n");
+        Ok(())
+    }
+
+    #[test]
+    fn string_raw() -> Result<(), Box<dyn Error>> {
+        let json = r#"{"a":"b"}"#;
+        let mut executor = Executor::new(".a")?;
+        let result = execute_str(&mut executor, json, FileFormat::Json, None)?;
+        assert_eq!(result, "b\n");
+        Ok(())
+    }
+
+    #[test]
+    fn string_raw_escapes() -> Result<(), Box<dyn Error>> {
+        let json = r#"{"a":"say \"hi\"\\n\tnow\u00e9 C:\\dir"}"#;
+        let mut executor = Executor::new(".a")?;
+        let result = execute_str(&mut executor, json, FileFormat::Json, None)?;
+        assert_eq!(result, "say \"hi\"\\n\tnowé C:\\dir\n");
+        Ok(())
+    }
+
+    #[test]
+    fn string_raw_keeps_whitespace() -> Result<(), Box<dyn Error>> {
+        let json = r#"{"a":"  padded \n"}"#;
+        let mut executor = Executor::new(".a")?;
+        let result = execute_str(&mut executor, json, FileFormat::Json, None)?;
+        assert_eq!(result, "  padded \n\n");
+        Ok(())
+    }
+
+    #[test]
+    fn raw_generator() -> Result<(), Box<dyn Error>> {
+        let json = r#"["a","b"]"#;
+        let mut executor = Executor::new(".[]")?;
+        let result = execute_str(&mut executor, json, FileFormat::Json, None)?;
+        assert_eq!(result, "a\nb\n");
+        Ok(())
+    }
+
+    #[test]
+    fn dedup() -> Result<(), Box<dyn Error>> {
+        let jsons = vec!["[1,1,2,1]".to_owned()];
+        let mut executor = Executor::new(".[]")?;
+        let mut dedup = |dedup| -> Result<String, Box<dyn Error>> {
+            let options = WriteOptions {
+                dedup: Some(dedup),
+                ..WriteOptions::default()
+            };
+            let mut buf = Vec::<u8>::new();
+            executor.execute(&jsons, Some(FileFormat::Json), &options, &mut buf)?;
+            Ok(String::from_utf8(buf)?)
+        };
+        assert_eq!(dedup(Dedup::Consecutive)?, "1\n2\n1\n");
+        assert_eq!(dedup(Dedup::Global)?, "1\n2\n");
+        Ok(())
+    }
 
-    use crate::{Executor, FileFormat, Input};
+    #[test]
+    fn numeric_output() -> Result<(), Box<dyn Error>> {
+        let jsons =
+            vec![r#"{"a":"42","b":["-1.5","1e3"],"c":"02134","d":" 7","e":"x","f":"12345678901234567890123"}"#.to_owned()];
+        let mut executor = Executor::new(".")?;
+        let options = WriteOptions {
+            numeric_output: true,
+            ..WriteOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        executor.execute(&jsons, Some(FileFormat::Json), &options, &mut buf)?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            r#"{"a":42,"b":[-1.5,"1e3"],"c":"02134","d":" 7","e":"x","f":"12345678901234567890123"}"#
+                .to_owned()
+                + "\n"
+        );
+        Ok(())
+    }
 
-    fn execute_str(
-        executor: &mut Executor,
-        value: &str,
-        input_format: FileFormat,
-        output_format: Option<FileFormat>,
-    ) -> Result<String, Box<dyn Error>> {
-        let jsons = input_format.read_to_json(Cursor::new(value.as_bytes()))?;
+    #[test]
+    fn pretty_threshold() -> Result<(), Box<dyn Error>> {
+        let options = WriteOptions {
+            pretty_threshold: Some(10),
+            ..WriteOptions::default()
+        };
         let mut buf = Vec::<u8>::new();
-        executor.execute(&jsons, output_format, false, &mut Cursor::new(&mut buf))?;
-        let result = String::from_utf8(buf)?;
-        Ok(result)
+        let small = vec![r#"{"a":1}"#.to_owned()];
+        FileFormat::Json.write_format(&small, &options, &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, "{\"a\":1}\n");
+        let mut buf = Vec::<u8>::new();
+        let large = vec![r#"{"a":1,"b":2}"#.to_owned()];
+        FileFormat::Json.write_format(&large, &options, &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, "{\n  \"a\": 1,\n  \"b\": 2\n}\n");
+        Ok(())
     }
 
     #[test]
-    fn file_format_from_extension() {
-        assert_eq!(
-            FileFormat::from_extension("json").unwrap(),
-            FileFormat::Json
-        );
-        assert_eq!(FileFormat::from_extension("ron").unwrap(), FileFormat::Ron);
-        assert_eq!(
-            FileFormat::from_extension("yaml").unwrap(),
-            FileFormat::Yaml
-        );
-        assert_eq!(FileFormat::from_extension("yml").unwrap(), FileFormat::Yaml);
-        assert_eq!(
-            FileFormat::from_extension("toml").unwrap(),
-            FileFormat::Toml
-        );
+    fn indent() -> Result<(), Box<dyn Error>> {
+        let values = vec![r#"{"a":{"b":1}}"#.to_owned()];
+        let written = |indent| -> Result<String, Box<dyn Error>> {
+            let options = WriteOptions {
+                pretty: true,
+                indent,
+                ..WriteOptions::default()
+            };
+            let mut buf = Vec::<u8>::new();
+            FileFormat::Json.write_format(&values, &options, &mut buf)?;
+            Ok(String::from_utf8(buf)?)
+        };
         assert_eq!(
-            FileFormat::from_extension("jsonl").unwrap(),
-            FileFormat::Json
+            written(Some(4))?,
+            "{\n    \"a\": {\n        \"b\": 1\n    }\n}\n"
         );
-        assert!(FileFormat::from_extension("garbage").is_err());
+        assert_eq!(written(Some(0))?, "{\"a\":{\"b\":1}}\n");
+        assert_eq!(written(None)?, "{\n  \"a\": {\n    \"b\": 1\n  }\n}\n");
+        Ok(())
     }
 
     #[test]
-    fn file_format_to_extension() {
-        assert_eq!(FileFormat::Json.to_extension(), "json");
-        assert_eq!(FileFormat::Yaml.to_extension(), "yaml");
-        assert_eq!(FileFormat::Toml.to_extension(), "toml");
-        assert_eq!(FileFormat::Ron.to_extension(), "ron");
+    fn collect_errors() -> Result<(), Box<dyn Error>> {
+        let jsons = vec!["1".to_owned(), "\"a\"".to_owned(), "3".to_owned()];
+        assert!(Executor::new(". + 1")?.run(&jsons).is_err());
+        let mut executor = Executor::new(". + 1")?;
+        executor.collect_errors = true;
+        assert_eq!(executor.run(&jsons)?, vec!["2\n", "4\n"]);
+        assert_eq!(executor.errors.len(), 1);
+        assert!(executor.errors[0].starts_with("document 2:"));
+        Ok(())
     }
 
     #[test]
-    fn identity_json() -> Result<(), Box<dyn Error>> {
-        let json = r#"{"a":"b"}"#;
+    fn collect_timeouts() -> Result<(), Box<dyn Error>> {
+        let program = "if . == 1 then (last(range(1e6)) | 1000) else . * 10 end";
+        let jsons: Vec<String> = ["1", "2", "3", "4"].map(str::to_owned).to_vec();
+        let mut executor = Executor::with_timeout(program, Duration::from_millis(50))?;
+        executor.collect_errors = true;
+        assert_eq!(executor.run(&jsons)?, vec!["20\n", "30\n", "40\n"]);
+        assert_eq!(executor.errors.len(), 1);
+        assert!(executor.errors[0].starts_with("document 1:"));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_output() -> Result<(), Box<dyn Error>> {
+        let options = WriteOptions {
+            validate_output: true,
+            ..WriteOptions::default()
+        };
+        let jsons = vec![r#"{"a":[1,{"b":"c"}],"d":"e: f"}"#.to_owned()];
         let mut executor = Executor::new(".")?;
-        let result = execute_str(
+        for format in [
+            FileFormat::Json,
+            FileFormat::Yaml,
+            FileFormat::Toml,
+            FileFormat::Ron,
+        ] {
+            executor.execute(&jsons, Some(format), &options, &mut Vec::<u8>::new())?;
+        }
+        assert!(FileFormat::Toml.validate_output(b"a = [1,\n").is_err());
+        assert!(FileFormat::Yaml.validate_output(b"a: [1\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn no_preserve_order() -> Result<(), Box<dyn Error>> {
+        let json = r#"{"b":1,"a":{"d":2,"c":3}}"#;
+        let mut executor = Executor::new(".")?;
+        let preserved = execute_str(
             &mut executor,
             json,
             FileFormat::Json,
             Some(FileFormat::Json),
         )?;
-        assert_eq!(result, format!("{json}\n"));
+        assert_eq!(preserved, json.to_owned() + "\n");
+        let args = <crate::Args as clap::Parser>::parse_from(["nuq", "--no-preserve-order", "."]);
+        let mut buf = Vec::<u8>::new();
+        executor.execute(
+            &[json.to_owned()],
+            Some(FileFormat::Json),
+            &args.write_options(),
+            &mut buf,
+        )?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "{\"a\":{\"c\":3,\"d\":2},\"b\":1}\n"
+        );
         Ok(())
     }
 
     #[test]
-    fn identity_yaml() -> Result<(), Box<dyn Error>> {
-        let yaml = "a: b";
+    fn toml_single_trailing_newline() -> Result<(), Box<dyn Error>> {
         let mut executor = Executor::new(".")?;
-        let result = execute_str(
-            &mut executor,
-            yaml,
-            FileFormat::Yaml,
-            Some(FileFormat::Yaml),
-        )?;
-        assert_eq!(result, format!("{yaml}\n"));
+        for json in [r#"{"a":1}"#, r#"{"a":{"b":[1,2]}}"#] {
+            let toml = execute_str(
+                &mut executor,
+                json,
+                FileFormat::Json,
+                Some(FileFormat::Toml),
+            )?;
+            assert!(toml.ends_with('\n') && !toml.ends_with("\n\n"), "{toml:?}");
+        }
         Ok(())
     }
 
     #[test]
-    fn identity_multi_yaml() -> Result<(), Box<dyn Error>> {
-        let yaml = "a: b\n---\na: c";
+    fn keys_only() -> Result<(), Box<dyn Error>> {
+        let options = WriteOptions {
+            keys_only: true,
+            ..WriteOptions::default()
+        };
         let mut executor = Executor::new(".")?;
-        let result = execute_str(
-            &mut executor,
-            yaml,
-            FileFormat::Yaml,
-            Some(FileFormat::Yaml),
-        )?;
-        assert_eq!(&result, "---\na: b\n---\na: c\n");
+        let mut buf = Vec::<u8>::new();
+        let object = vec![r#"{"b":{"c":1},"a":2}"#.to_owned()];
+        executor.execute(&object, Some(FileFormat::Json), &options, &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, "[\"b\",\"a\"]\n");
+        let array = vec!["[1]".to_owned()];
+        let err = executor
+            .execute(
+                &array,
+                Some(FileFormat::Json),
+                &options,
+                &mut Vec::<u8>::new(),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("requires object documents"));
         Ok(())
     }
 
     #[test]
-    fn identity_ron() -> Result<(), Box<dyn Error>> {
-        let ron = r#"(a: "b")"#;
-        let mut executor = Executor::new(".")?;
-        let result = execute_str(&mut executor, ron, FileFormat::Ron, Some(FileFormat::Ron))?;
-        assert_eq!(result, "{\"a\":\"b\"}\n");
+    fn nth() -> Result<(), Box<dyn Error>> {
+        let jsons = vec!["[1,2,3]".to_owned()];
+        let mut executor = Executor::new(".[]")?;
+        let mut nth = |nth| -> Result<String, Box<dyn Error>> {
+            let options = WriteOptions {
+                nth: Some(nth),
+                ..WriteOptions::default()
+            };
+            let mut buf = Vec::<u8>::new();
+            executor.execute(&jsons, Some(FileFormat::Json), &options, &mut buf)?;
+            Ok(String::from_utf8(buf)?)
+        };
+        assert_eq!(nth(1)?, "2\n");
+        assert_eq!(nth(3)?, "");
         Ok(())
     }
 
     #[test]
-    fn identity_toml() -> Result<(), Box<dyn Error>> {
-        let ron = r#"a = "b""#;
+    fn no_control_chars() -> Result<(), Box<dyn Error>> {
         let mut executor = Executor::new(".")?;
-        let result = execute_str(&mut executor, ron, FileFormat::Toml, Some(FileFormat::Toml))?;
-        assert_eq!(result, "a = \"b\"\n");
+        let options = WriteOptions {
+            no_control_chars: true,
+            ..WriteOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        let clean = vec![r#"{"a":"x\ty\n"}"#.to_owned()];
+        executor.execute(&clean, Some(FileFormat::Json), &options, &mut buf)?;
+        let dirty = vec![r#"{"a":["bell\u0007"]}"#.to_owned()];
+        let err = executor
+            .execute(&dirty, Some(FileFormat::Json), &options, &mut buf)
+            .unwrap_err();
+        assert!(err.to_string().contains("control character"));
         Ok(())
     }
 
     #[test]
-    fn string_json() -> Result<(), Box<dyn Error>> {
-        let json = r#"{"a":"b"}"#;
-        let mut executor = Executor::new(".a")?;
+    fn canonical_yaml() -> Result<(), Box<dyn Error>> {
+        let args = <crate::Args as clap::Parser>::parse_from(["nuq", "--k8s", "."]);
+        let jsons = vec![
+            r#"{"kind":"Service","apiVersion":"v1","metadata":{"name":"web","labels":{"on":"yes"}}}"#
+                .to_owned(),
+            r#"{"kind":"Deployment","apiVersion":"apps/v1","spec":{"replicas":2,"ports":["80"]}}"#
+                .to_owned(),
+        ];
+        let mut buf = Vec::<u8>::new();
+        Executor::new(".")?.execute(
+            &jsons,
+            Some(FileFormat::Yaml),
+            &args.write_options(),
+            &mut buf,
+        )?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            r#"---
+apiVersion: v1
+kind: Service
+metadata:
+  labels:
+    "on": "yes"
+  name: web
+---
+apiVersion: apps/v1
+kind: Deployment
+spec:
+  ports:
+  - "80"
+  replicas: 2
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn map_values() -> Result<(), Box<dyn Error>> {
+        let args =
+            <crate::Args as clap::Parser>::parse_from(["nuq", "--map-values", "tostring", "."]);
+        let mut executor = Executor::new(&args.jq_program()?)?;
+        let json = r#"{"a":1,"b":true,"c":"d"}"#;
         let result = execute_str(
             &mut executor,
             json,
             FileFormat::Json,
             Some(FileFormat::Json),
         )?;
-        assert_eq!(result, "\"b\"\n");
+        assert_eq!(result, r#"{"a":"1","b":"true","c":"d"}"#.to_owned() + "\n");
         Ok(())
     }
 
     #[test]
-    fn string_raw() -> Result<(), Box<dyn Error>> {
+    fn arg() -> Result<(), Box<dyn Error>> {
+        let args = <crate::Args as clap::Parser>::parse_from([
+            "nuq",
+            "--arg",
+            "x",
+            "hello",
+            "--arg",
+            "y",
+            "a\"b",
+            ".foo = $x | .bar = $y",
+        ]);
+        let mut executor = Executor::new(&args.jq_program()?)?;
+        let result = execute_str(&mut executor, "{}", FileFormat::Json, None)?;
+        assert!(result.contains(r#""foo":"hello""#));
+        assert!(result.contains(r#""bar":"a\"b""#));
+        let args = <crate::Args as clap::Parser>::parse_from(["nuq", "--arg", "1x", "a", "."]);
+        assert!(args.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn compact_json_is_validated() {
+        let mut buf = Vec::<u8>::new();
+        let options = WriteOptions::default();
+        assert!(super::write_json(&["{\"a\":1\n".to_owned()], &options, &mut buf).is_err());
+        assert!(super::write_json(&["[1]\n".to_owned()], &options, &mut buf).is_ok());
+    }
+
+    #[test]
+    fn raw_input_format() {
+        for flag in ["-i", "--format", "--only"] {
+            let args = <crate::Args as clap::Parser>::try_parse_from(["nuq", flag, "raw", "."]);
+            assert!(args.is_err());
+        }
+        let args = <crate::Args as clap::Parser>::try_parse_from(["nuq", "-i", "y", "."]);
+        assert_eq!(args.unwrap().input_format(), Some(FileFormat::Yaml));
+    }
+
+    #[test]
+    fn argjson() -> Result<(), Box<dyn Error>> {
+        let args = <crate::Args as clap::Parser>::parse_from([
+            "nuq",
+            "--argjson",
+            "n",
+            "5",
+            "--argjson",
+            "o",
+            r#"{"a":[-1]}"#,
+            ".x = $n | .y = $o",
+        ]);
+        let mut executor = Executor::new(&args.jq_program()?)?;
+        let result = execute_str(&mut executor, "{}", FileFormat::Json, None)?;
+        assert_eq!(result, r#"{"x":5,"y":{"a":[-1]}}"#.to_owned() + "\n");
+        let args = <crate::Args as clap::Parser>::parse_from(["nuq", "--argjson", "n", "{", "."]);
+        let err = args.jq_program().expect_err("malformed json is rejected");
+        assert!(err.to_string().contains("invalid json for --argjson n"));
+        Ok(())
+    }
+
+    #[test]
+    fn entries() -> Result<(), Box<dyn Error>> {
+        let args = <crate::Args as clap::Parser>::parse_from(["nuq", "--entries", "."]);
+        let mut executor = Executor::new(&args.jq_program()?)?;
+        let entries = execute_str(&mut executor, r#"{"a":1,"b":"c"}"#, FileFormat::Json, None)?;
+        assert_eq!(
+            entries,
+            r#"[{"key":"a","value":1},{"key":"b","value":"c"}]"#.to_owned() + "\n"
+        );
+        let args = <crate::Args as clap::Parser>::parse_from(["nuq", "--from-entries", "."]);
+        let mut executor = Executor::new(&args.jq_program()?)?;
+        let object = execute_str(&mut executor, entries.trim(), FileFormat::Json, None)?;
+        assert_eq!(object, r#"{"a":1,"b":"c"}"#.to_owned() + "\n");
+        Ok(())
+    }
+
+    #[test]
+    fn null_on_missing() -> Result<(), Box<dyn Error>> {
         let json = r#"{"a":"b"}"#;
-        let mut executor = Executor::new(".a")?;
-        let result = execute_str(&mut executor, json, FileFormat::Json, None)?;
-        assert_eq!(result, "b\n");
+        let args = <crate::Args as clap::Parser>::parse_from(["nuq", ".a.b.c"]);
+        let mut executor = Executor::new(&args.jq_program()?)?;
+        assert!(execute_str(&mut executor, json, FileFormat::Json, None).is_err());
+        let args =
+            <crate::Args as clap::Parser>::parse_from(["nuq", "--null-on-missing", ".a.b.c"]);
+        let mut executor = Executor::new(&args.jq_program()?)?;
+        assert_eq!(
+            execute_str(&mut executor, json, FileFormat::Json, None)?,
+            "null\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn humanize_numbers() -> Result<(), Box<dyn Error>> {
+        let jsons = vec![r#"[1000000,-1234,999,1.5,"12345","00123","+1000"]"#.to_owned()];
+        let mut executor = Executor::new(".[]")?;
+        let mut humanized = |humanize_numbers| -> Result<String, Box<dyn Error>> {
+            let options = WriteOptions {
+                humanize_numbers,
+                ..WriteOptions::default()
+            };
+            let mut buf = Vec::<u8>::new();
+            executor.execute(&jsons, None, &options, &mut buf)?;
+            Ok(String::from_utf8(buf)?)
+        };
+        assert_eq!(
+            humanized(true)?,
+            "1,000,000\n-1,234\n999\n1.5\n12345\n00123\n+1000\n"
+        );
+        assert_eq!(
+            humanized(false)?,
+            "1000000\n-1234\n999\n1.5\n12345\n00123\n+1000\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn wrap() -> Result<(), Box<dyn Error>> {
+        let jsons = vec![r#"["the quick brown fox jumps","abcdefghijkl"]"#.to_owned()];
+        let mut executor = Executor::new(".[]")?;
+        let options = WriteOptions {
+            wrap: Some(10),
+            ..WriteOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        executor.execute(&jsons, None, &options, &mut buf)?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "the quick\nbrown fox\njumps\nabcdefghij\nkl\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn raw_prefix_suffix() -> Result<(), Box<dyn Error>> {
+        let jsons = vec![r#"["a","b"]"#.to_owned()];
+        let mut executor = Executor::new(".[]")?;
+        let options = WriteOptions {
+            prefix: "rm ".to_owned(),
+            suffix: ";".to_owned(),
+            ..WriteOptions::default()
+        };
+        let mut buf = Vec::<u8>::new();
+        executor.execute(&jsons, None, &options, &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, "rm a;\nrm b;\n");
         Ok(())
     }
 
@@ -561,30 +3581,256 @@ mod test {
             ext: String::new(),
             reader: Box::new(Cursor::new(r#"{"a":"b"}"#)),
             format: Some(FileFormat::Json),
+            name: None,
         };
         let yaml = Input {
             ext: String::new(),
             reader: Box::new(Cursor::new("c: d")),
             format: Some(FileFormat::Yaml),
+            name: None,
         };
-        let array = super::slurp(&mut [json, yaml])?;
+        let array = super::slurp(&mut [json, yaml], &ReadOptions::default())?;
         assert_eq!(array, r#"[{"a":"b"},{"c":"d"}]"#);
         Ok(())
     }
 
+    #[test]
+    fn raw_input() -> Result<(), Box<dyn Error>> {
+        let options = ReadOptions {
+            raw_input: true,
+            ..ReadOptions::default()
+        };
+        let text = || Input {
+            ext: "yaml".to_owned(),
+            reader: Box::new(Cursor::new("a: 1\r\n\"b\"\n\nc")),
+            format: None,
+            name: None,
+        };
+        let docs = text().read_to_docs(&options)?;
+        assert_eq!(docs.jsons, [r#""a: 1""#, r#""\"b\"""#, r#""""#, r#""c""#]);
+        assert_eq!(docs.input_format, FileFormat::Json);
+        let array = super::slurp(&mut [text(), text()], &options)?;
+        let value: serde_json::Value = serde_json::from_str(&array)?;
+        assert_eq!(
+            value,
+            serde_json::json!(["a: 1", "\"b\"", "", "c", "a: 1", "\"b\"", "", "c"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn merge() -> Result<(), Box<dyn Error>> {
+        let json = Input {
+            ext: String::new(),
+            reader: Box::new(Cursor::new(r#"{"a":{"b":[1]}}"#)),
+            format: Some(FileFormat::Json),
+            name: None,
+        };
+        let yaml = Input {
+            ext: String::new(),
+            reader: Box::new(Cursor::new("a:\n  b: [2]\n---\nc: d")),
+            format: Some(FileFormat::Yaml),
+            name: None,
+        };
+        let merged = super::merge(
+            &mut [json, yaml],
+            &ReadOptions::default(),
+            crate::merge::ArrayMerge::Concat,
+        )?;
+        assert_eq!(merged, r#"{"a":{"b":[1,2]},"c":"d"}"#);
+        Ok(())
+    }
+
     #[test]
     fn guess() {
         let mut json = Input {
             ext: String::new(),
             reader: Box::new(Cursor::new(r#"{"a":"b"}"#)),
             format: None,
+            name: None,
         };
-        assert!(json.read_to_docs().is_ok());
+        assert!(json.read_to_docs(&ReadOptions::default()).is_ok());
         let mut yaml = Input {
             ext: String::new(),
             reader: Box::new(Cursor::new("c: d")),
             format: None,
+            name: None,
+        };
+        assert!(yaml.read_to_docs(&ReadOptions::default()).is_ok());
+        let mut xml = Input {
+            ext: String::new(),
+            reader: Box::new(Cursor::new("<a><b>c</b></a>")),
+            format: None,
+            name: None,
+        };
+        let docs = xml.read_to_docs(&ReadOptions::default()).unwrap();
+        assert_eq!(docs.input_format, FileFormat::Xml);
+        assert_eq!(docs.jsons, vec![r#"{"a":{"b":"c"}}"#]);
+        let mut msgpack = Input {
+            ext: String::new(),
+            reader: Box::new(Cursor::new([0x81, 0xa1, b'a', 0xc3])),
+            format: None,
+            name: None,
+        };
+        let docs = msgpack.read_to_docs(&ReadOptions::default()).unwrap();
+        assert_eq!(docs.input_format, FileFormat::MessagePack);
+        assert_eq!(docs.jsons, vec![r#"{"a":true}"#]);
+    }
+
+    #[test]
+    fn modeline() {
+        let input = || Input {
+            ext: String::new(),
+            reader: Box::new(Cursor::new("a = 1\n# vim: ft=toml\n")),
+            format: None,
+            name: None,
+        };
+        let docs = input().read_to_docs(&ReadOptions::default()).unwrap();
+        assert_eq!(docs.input_format, FileFormat::Yaml);
+        let options = ReadOptions {
+            use_modeline: true,
+            ..Default::default()
+        };
+        let docs = input().read_to_docs(&options).unwrap();
+        assert_eq!(docs.input_format, FileFormat::Toml);
+        assert_eq!(docs.jsons, vec![r#"{"a":1}"#]);
+    }
+
+    #[test]
+    fn explain_ambiguous_guess() -> Result<(), Box<dyn Error>> {
+        let mut buf = Vec::<u8>::new();
+        super::explain_guess(b"a = 1", &ReadOptions::default(), &mut buf)?;
+        let explanation = String::from_utf8(buf)?;
+        let lines: Vec<&str> = explanation.lines().collect();
+        assert!(lines[0].starts_with("json: "));
+        assert_eq!(lines[3], "yaml: ok, 1 document(s)");
+        assert_eq!(lines[5], "toml: ok, 1 document(s)");
+        assert_eq!(lines[9], "guessed yaml");
+        Ok(())
+    }
+
+    #[test]
+    fn strict_json_duplicate_key() {
+        let options = ReadOptions {
+            strict_json: true,
+            ..ReadOptions::default()
+        };
+        let json = r#"{"a":1,"a":2}"#;
+        let err = FileFormat::Json
+            .read_to_json(Cursor::new(json), &options)
+            .unwrap_err();
+        assert!(err.to_string().contains("duplicate key \"a\""));
+        assert!(FileFormat::Json
+            .read_to_json(Cursor::new(json), &ReadOptions::default())
+            .is_ok());
+    }
+
+    fn limited(head: Option<usize>, tail: Option<usize>) -> Result<Vec<String>, Box<dyn Error>> {
+        let yaml = "a: 1\n---\na: 2\n---\na: 3";
+        let jsons = FileFormat::Yaml.read_to_json(Cursor::new(yaml), &ReadOptions::default())?;
+        let mut documents = vec![
+            JsonDocuments::new(jsons, FileFormat::Yaml),
+            JsonDocuments::new(vec![r#"{"a":4}"#.to_owned()], FileFormat::Json),
+        ];
+        super::limit_documents(&mut documents, head, tail);
+        Ok(documents.into_iter().flat_map(|docs| docs.jsons).collect())
+    }
+
+    #[test]
+    fn head_and_tail() -> Result<(), Box<dyn Error>> {
+        assert_eq!(limited(Some(2), None)?, vec![r#"{"a":1}"#, r#"{"a":2}"#]);
+        assert_eq!(limited(None, Some(2))?, vec![r#"{"a":3}"#, r#"{"a":4}"#]);
+        assert_eq!(limited(Some(3), Some(1))?, vec![r#"{"a":3}"#]);
+        assert_eq!(limited(None, None)?.len(), 4);
+        assert!(limited(Some(0), None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn random_sample_with_seed() {
+        let sample = |seed| {
+            let documents = [(0..10), (10..20)].map(|range| {
+                let jsons = range.map(|idx: usize| idx.to_string()).collect();
+                Ok(JsonDocuments::new(jsons, FileFormat::Json))
+            });
+            let mut rng = crate::sample::Rng::new(seed);
+            let sampled = super::sample_documents(Box::new(documents.into_iter()), 3, &mut rng);
+            sampled
+                .unwrap()
+                .into_iter()
+                .map(|docs| docs.jsons)
+                .collect::<Vec<_>>()
         };
-        assert!(yaml.read_to_docs().is_ok());
+        assert_eq!(sample(7), sample(7));
+        assert_eq!(sample(7).len(), 2);
+        assert_eq!(sample(7).concat().len(), 3);
+    }
+
+    #[test]
+    fn no_color() {
+        let json = Some(FileFormat::Json);
+        let args = <crate::Args as clap::Parser>::parse_from(["nuq", "."]);
+        assert!(args.color_decision(json, false, true));
+        assert!(!args.color_decision(json, true, true));
+        assert!(!args.color_decision(json, false, false));
+        let args = <crate::Args as clap::Parser>::parse_from(["nuq", "-c", "true", "."]);
+        assert!(args.color_decision(json, true, false));
+    }
+
+    #[test]
+    fn explode_keys() -> Result<(), Box<dyn Error>> {
+        let jsons = vec![r#"{"a":1,"b":{"c":2},"d":[3]}"#.to_owned(), "4".to_owned()];
+        assert_eq!(
+            super::explode_keys(jsons)?,
+            vec![r#"{"a":1}"#, r#"{"b":{"c":2}}"#, r#"{"d":[3]}"#, "4"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn count_by() -> Result<(), Box<dyn Error>> {
+        let jsons = [
+            r#"{"status":"ok"}"#,
+            r#"{"status":"failed"}"#,
+            r#"{"status":"ok"}"#,
+            r#"{"status":404}"#,
+            "{}",
+        ]
+        .map(str::to_owned);
+        assert_eq!(
+            super::count_documents(&jsons, ".status")?,
+            r#"{"ok":2,"failed":1,"404":1,"null":1}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sort_by() -> Result<(), Box<dyn Error>> {
+        let mut jsons: Vec<String> = [
+            r#"{"n":2,"id":"a"}"#,
+            r#"{"id":"b"}"#,
+            r#"{"n":1,"id":"c"}"#,
+        ]
+        .map(str::to_owned)
+        .to_vec();
+        super::sort_documents(&mut jsons, ".n", false)?;
+        assert_eq!(
+            jsons,
+            vec![
+                r#"{"id":"b"}"#,
+                r#"{"n":1,"id":"c"}"#,
+                r#"{"n":2,"id":"a"}"#
+            ]
+        );
+        super::sort_documents(&mut jsons, ".n", true)?;
+        assert_eq!(
+            jsons,
+            vec![
+                r#"{"n":2,"id":"a"}"#,
+                r#"{"n":1,"id":"c"}"#,
+                r#"{"id":"b"}"#
+            ]
+        );
+        Ok(())
     }
 }
@@ -1,5 +1,14 @@
+use std::process::ExitCode;
+
 use clap::Parser;
 
-fn main() -> anyhow::Result<()> {
-    nuq::run(&nuq::Args::parse())
+fn main() -> ExitCode {
+    let args = nuq::Args::parse();
+    match nuq::run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            args.report(&err);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,88 @@
+use clap::ValueEnum;
+use serde_json::Value;
+
+/// Strategy to combine two arrays at the same position while deep merging.
+/// Replace keeps the later array, concat appends the later array to the
+/// earlier one and union does the same but skips elements already present.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ArrayMerge {
+    #[default]
+    Replace,
+    Concat,
+    Union,
+}
+
+/// Recursively merges other into base. Objects are merged key by key,
+/// arrays according to the given strategy and any other value in other
+/// replaces the value in base.
+pub fn deep_merge(base: &mut Value, other: Value, arrays: ArrayMerge) {
+    match (base, other) {
+        (Value::Object(base), Value::Object(other)) => {
+            for (key, value) in other {
+                match base.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value, arrays),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(other)) => match arrays {
+            ArrayMerge::Replace => *base = other,
+            ArrayMerge::Concat => base.extend(other),
+            ArrayMerge::Union => {
+                for value in other {
+                    if !base.contains(&value) {
+                        base.push(value);
+                    }
+                }
+            }
+        },
+        (base, other) => *base = other,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use super::{deep_merge, ArrayMerge};
+
+    fn merge(arrays: ArrayMerge) -> Value {
+        let mut base = json!({"a": {"b": 1, "c": [1, 2]}, "d": "e"});
+        let other = json!({"a": {"c": [2, 3], "f": true}, "d": "g"});
+        deep_merge(&mut base, other, arrays);
+        base
+    }
+
+    #[test]
+    fn replace() {
+        assert_eq!(
+            merge(ArrayMerge::Replace),
+            json!({"a": {"b": 1, "c": [2, 3], "f": true}, "d": "g"})
+        );
+    }
+
+    #[test]
+    fn concat() {
+        assert_eq!(
+            merge(ArrayMerge::Concat),
+            json!({"a": {"b": 1, "c": [1, 2, 2, 3], "f": true}, "d": "g"})
+        );
+    }
+
+    #[test]
+    fn union() {
+        assert_eq!(
+            merge(ArrayMerge::Union),
+            json!({"a": {"b": 1, "c": [1, 2, 3], "f": true}, "d": "g"})
+        );
+    }
+
+    #[test]
+    fn mismatched_types() {
+        let mut base = json!({"a": [1]});
+        deep_merge(&mut base, json!({"a": {"b": 2}}), ArrayMerge::Concat);
+        assert_eq!(base, json!({"a": {"b": 2}}));
+    }
+}
@@ -0,0 +1,288 @@
+use std::io::Write;
+
+use base64::Engine;
+use serde_json::{Map, Value};
+
+/// The nesting depth of arrays and maps, beyond which the input is rejected
+/// instead of overflowing the stack. It matches `serde_json`.
+const MAX_DEPTH: usize = 128;
+
+/// Reads a stream of concatenated message pack values. Binary data becomes
+/// a base64 encoded string like plist data and map keys, which are not
+/// strings, are converted to their json text. Extension types are not
+/// supported.
+pub fn read(bytes: &[u8], limit: usize) -> anyhow::Result<Vec<Value>> {
+    let mut decoder = Decoder {
+        bytes,
+        pos: 0,
+        depth: 0,
+    };
+    let mut values = Vec::new();
+    while decoder.pos < bytes.len() && values.len() < limit {
+        values.push(decoder.value()?);
+    }
+    Ok(values)
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| anyhow::anyhow!("unexpected end of message pack input"))?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into()?)
+    }
+
+    fn len(&mut self, width: usize) -> anyhow::Result<usize> {
+        Ok(match width {
+            1 => usize::from(self.array::<1>()?[0]),
+            2 => usize::from(u16::from_be_bytes(self.array()?)),
+            _ => usize::try_from(u32::from_be_bytes(self.array()?))?,
+        })
+    }
+
+    fn value(&mut self) -> anyhow::Result<Value> {
+        let marker = self.array::<1>()?[0];
+        Ok(match marker {
+            0x00..=0x7f => Value::from(marker),
+            0x80..=0x8f => self.map(usize::from(marker & 0x0f))?,
+            0x90..=0x9f => self.seq(usize::from(marker & 0x0f))?,
+            0xa0..=0xbf => self.string(usize::from(marker & 0x1f))?,
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xc4..=0xc6 => {
+                let len = self.len(1 << (marker - 0xc4))?;
+                Value::String(base64::engine::general_purpose::STANDARD.encode(self.take(len)?))
+            }
+            0xca => float(f64::from(f32::from_be_bytes(self.array()?)))?,
+            0xcb => float(f64::from_be_bytes(self.array()?))?,
+            0xcc => Value::from(self.array::<1>()?[0]),
+            0xcd => Value::from(u16::from_be_bytes(self.array()?)),
+            0xce => Value::from(u32::from_be_bytes(self.array()?)),
+            0xcf => Value::from(u64::from_be_bytes(self.array()?)),
+            0xd0 => Value::from(i8::from_be_bytes(self.array()?)),
+            0xd1 => Value::from(i16::from_be_bytes(self.array()?)),
+            0xd2 => Value::from(i32::from_be_bytes(self.array()?)),
+            0xd3 => Value::from(i64::from_be_bytes(self.array()?)),
+            0xd9..=0xdb => {
+                let len = self.len(1 << (marker - 0xd9))?;
+                self.string(len)?
+            }
+            0xdc | 0xdd => {
+                let len = self.len(if marker == 0xdc { 2 } else { 4 })?;
+                self.seq(len)?
+            }
+            0xde | 0xdf => {
+                let len = self.len(if marker == 0xde { 2 } else { 4 })?;
+                self.map(len)?
+            }
+            0xe0..=0xff => Value::from(i8::from_be_bytes([marker])),
+            0xc7..=0xc9 | 0xd4..=0xd8 => {
+                anyhow::bail!("message pack extension types are not supported")
+            }
+            0xc1 => anyhow::bail!("invalid message pack marker 0xc1"),
+        })
+    }
+
+    fn string(&mut self, len: usize) -> anyhow::Result<Value> {
+        Ok(Value::String(String::from_utf8(self.take(len)?.to_vec())?))
+    }
+
+    fn nest(&mut self) -> anyhow::Result<()> {
+        if self.depth == MAX_DEPTH {
+            anyhow::bail!("message pack nesting deeper than {}", MAX_DEPTH);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn seq(&mut self, len: usize) -> anyhow::Result<Value> {
+        self.nest()?;
+        let mut array = Vec::new();
+        for _ in 0..len {
+            array.push(self.value()?);
+        }
+        self.depth -= 1;
+        Ok(Value::Array(array))
+    }
+
+    fn map(&mut self, len: usize) -> anyhow::Result<Value> {
+        self.nest()?;
+        let mut map = Map::new();
+        for _ in 0..len {
+            let key = match self.value()? {
+                Value::String(key) => key,
+                other => other.to_string(),
+            };
+            map.insert(key, self.value()?);
+        }
+        self.depth -= 1;
+        Ok(Value::Object(map))
+    }
+}
+
+fn float(float: f64) -> anyhow::Result<Value> {
+    serde_json::Number::from_f64(float)
+        .map(Value::Number)
+        .ok_or_else(|| anyhow::anyhow!("message pack float {} is not a valid json number", float))
+}
+
+/// Writes a value in the most compact message pack representation.
+pub fn write<W: Write>(value: &Value, writer: &mut W) -> anyhow::Result<()> {
+    match value {
+        Value::Null => writer.write_all(&[0xc0])?,
+        Value::Bool(bool) => writer.write_all(&[if *bool { 0xc3 } else { 0xc2 }])?,
+        Value::Number(number) => {
+            if let Some(unsigned) = number.as_u64() {
+                write_unsigned(unsigned, writer)?;
+            } else if let Some(signed) = number.as_i64() {
+                write_signed(signed, writer)?;
+            } else {
+                writer.write_all(&[0xcb])?;
+                writer.write_all(&number.as_f64().unwrap_or_default().to_be_bytes())?;
+            }
+        }
+        Value::String(string) => {
+            write_len(string.len(), [0xa0, 0xd9, 0xda, 0xdb], writer)?;
+            writer.write_all(string.as_bytes())?;
+        }
+        Value::Array(array) => {
+            write_len(array.len(), [0x90, 0, 0xdc, 0xdd], writer)?;
+            for element in array {
+                write(element, writer)?;
+            }
+        }
+        Value::Object(map) => {
+            write_len(map.len(), [0x80, 0, 0xde, 0xdf], writer)?;
+            for (key, value) in map {
+                write(&Value::String(key.clone()), writer)?;
+                write(value, writer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_unsigned<W: Write>(value: u64, writer: &mut W) -> anyhow::Result<()> {
+    if let Ok(fixint @ 0..=0x7f) = u8::try_from(value) {
+        writer.write_all(&[fixint])?;
+    } else if let Ok(value) = u8::try_from(value) {
+        writer.write_all(&[0xcc, value])?;
+    } else if let Ok(value) = u16::try_from(value) {
+        writer.write_all(&[0xcd])?;
+        writer.write_all(&value.to_be_bytes())?;
+    } else if let Ok(value) = u32::try_from(value) {
+        writer.write_all(&[0xce])?;
+        writer.write_all(&value.to_be_bytes())?;
+    } else {
+        writer.write_all(&[0xcf])?;
+        writer.write_all(&value.to_be_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_signed<W: Write>(value: i64, writer: &mut W) -> anyhow::Result<()> {
+    if let Ok(fixint @ -32..=-1) = i8::try_from(value) {
+        writer.write_all(&fixint.to_be_bytes())?;
+    } else if let Ok(value) = i8::try_from(value) {
+        writer.write_all(&[0xd0])?;
+        writer.write_all(&value.to_be_bytes())?;
+    } else if let Ok(value) = i16::try_from(value) {
+        writer.write_all(&[0xd1])?;
+        writer.write_all(&value.to_be_bytes())?;
+    } else if let Ok(value) = i32::try_from(value) {
+        writer.write_all(&[0xd2])?;
+        writer.write_all(&value.to_be_bytes())?;
+    } else {
+        writer.write_all(&[0xd3])?;
+        writer.write_all(&value.to_be_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes the marker of a string, array or map with the given length. The
+/// markers are the fix variant, whose length is in the lower bits, and
+/// the variants with an 8, 16 and 32 bit length.
+fn write_len<W: Write>(len: usize, markers: [u8; 4], writer: &mut W) -> anyhow::Result<()> {
+    let fix_max = if markers[0] == 0xa0 { 31 } else { 15 };
+    if len <= fix_max {
+        // fix_max fits into the lower bits of the marker
+        writer.write_all(&[markers[0] | u8::try_from(len)?])?;
+    } else if let (Ok(len), true) = (u8::try_from(len), markers[1] != 0) {
+        writer.write_all(&[markers[1], len])?;
+    } else if let Ok(len) = u16::try_from(len) {
+        writer.write_all(&[markers[2]])?;
+        writer.write_all(&len.to_be_bytes())?;
+    } else {
+        writer.write_all(&[markers[3]])?;
+        writer.write_all(&u32::try_from(len)?.to_be_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use serde_json::json;
+
+    use super::{read, write};
+
+    #[test]
+    fn round_trip() -> Result<(), Box<dyn Error>> {
+        let value = json!({
+            "nil": null,
+            "bools": [true, false],
+            "ints": [0, 127, 128, 65536, 5_000_000_000_u64, -1, -33, -40000, i64::MIN],
+            "float": 1.5,
+            "long": "x".repeat(300),
+            "nested": {"empty": [], "map": {}},
+        });
+        let mut buf = Vec::<u8>::new();
+        write(&value, &mut buf)?;
+        write(&json!("second"), &mut buf)?;
+        assert_eq!(read(&buf, usize::MAX)?, vec![value, json!("second")]);
+        assert_eq!(read(&buf, 1)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn compact_encoding() -> Result<(), Box<dyn Error>> {
+        let mut buf = Vec::<u8>::new();
+        write(&json!({"a": [1, -1]}), &mut buf)?;
+        assert_eq!(buf, [0x81, 0xa1, b'a', 0x92, 0x01, 0xff]);
+        Ok(())
+    }
+
+    #[test]
+    fn binary_and_truncated() -> Result<(), Box<dyn Error>> {
+        assert_eq!(read(&[0xc4, 0x02, 0x00, 0xff], 1)?, vec![json!("AP8=")]);
+        assert!(read(&[0x92, 0x01], 1).is_err());
+        assert!(read(&[0xd4, 0x01, 0x00], 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn depth() -> Result<(), Box<dyn Error>> {
+        let mut nested = vec![0x91; 127];
+        nested.push(0x90);
+        assert!(read(&nested, 1)?[0].is_array());
+        assert!(read(&vec![0x91; 100_000], 1).is_err());
+        assert!(read(&[0x81, 0xc0].repeat(100_000), 1).is_err());
+        Ok(())
+    }
+}
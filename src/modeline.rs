@@ -0,0 +1,73 @@
+/// Number of lines at the start and the end of a file searched for a
+/// modeline, like vim does by default.
+const SEARCHED_LINES: usize = 5;
+
+/// Finds the file type given by a vim (`vim: ft=yaml`, `vim: set
+/// filetype=yaml :`) or emacs (`-*- mode: yaml -*-`) modeline in the first
+/// or last lines of text.
+pub fn file_type(text: &str) -> Option<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let tail = lines
+        .len()
+        .saturating_sub(SEARCHED_LINES)
+        .max(SEARCHED_LINES);
+    lines
+        .iter()
+        .take(SEARCHED_LINES)
+        .chain(lines.iter().skip(tail))
+        .find_map(|line| vim(line).or_else(|| emacs(line)))
+        .map(str::to_lowercase)
+}
+
+fn vim(line: &str) -> Option<&str> {
+    let (_, options) = ["vim:", "vi:", "ex:"]
+        .iter()
+        .find_map(|marker| line.split_once(marker))?;
+    options
+        .split(|char: char| char == ':' || char.is_whitespace())
+        .find_map(|option| {
+            let (key, value) = option.split_once('=')?;
+            matches!(key, "ft" | "filetype" | "syntax" | "syn").then_some(value)
+        })
+}
+
+fn emacs(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (variables, _) = rest.split_once("-*-")?;
+    // either only the mode or a list of variables
+    if !variables.contains(':') {
+        return Some(variables.trim());
+    }
+    variables.split(';').find_map(|variable| {
+        let (key, value) = variable.split_once(':')?;
+        (key.trim() == "mode").then_some(value.trim())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::file_type;
+
+    #[test]
+    fn modelines() {
+        assert_eq!(
+            file_type("a = 1\n# vim: ft=toml\n").as_deref(),
+            Some("toml")
+        );
+        assert_eq!(
+            file_type("# vim: set filetype=yaml ts=2 :\na: 1").as_deref(),
+            Some("yaml")
+        );
+        assert_eq!(
+            file_type("# -*- mode: YAML; tab-width: 2 -*-").as_deref(),
+            Some("yaml")
+        );
+        assert_eq!(file_type("// -*- json -*-\n{}").as_deref(), Some("json"));
+        let middle = format!(
+            "{}# vim: ft=toml\n{}",
+            "a: 1\n".repeat(5),
+            "b: 2\n".repeat(5)
+        );
+        assert_eq!(file_type(&middle), None);
+    }
+}
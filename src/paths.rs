@@ -0,0 +1,118 @@
+use std::io::Write;
+
+use serde_json::Value;
+
+/// Collects every leaf of value together with its path in jq notation.
+/// Empty objects and arrays are considered leaves.
+pub fn leaf_paths(value: &Value) -> Vec<(String, &Value)> {
+    let mut leaves = Vec::new();
+    collect(value, String::new(), &mut leaves);
+    leaves
+}
+
+fn collect<'a>(value: &'a Value, path: String, leaves: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                let segment = key_segment(key, path.is_empty());
+                collect(child, format!("{path}{segment}"), leaves);
+            }
+        }
+        Value::Array(array) if !array.is_empty() => {
+            for (idx, child) in array.iter().enumerate() {
+                let prefix = if path.is_empty() { "." } else { "" };
+                collect(child, format!("{path}{prefix}[{idx}]"), leaves);
+            }
+        }
+        _ => {
+            let path = if path.is_empty() {
+                ".".to_owned()
+            } else {
+                path
+            };
+            leaves.push((path, value));
+        }
+    }
+}
+
+/// Keys which are valid identifiers can use the short .key form,
+/// everything else needs to be quoted.
+fn key_segment(key: &str, root: bool) -> String {
+    let mut chars = key.chars();
+    let is_ident = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_ident {
+        format!(".{key}")
+    } else {
+        let prefix = if root { "." } else { "" };
+        format!("{prefix}[{}]", Value::String(key.to_owned()))
+    }
+}
+
+/// Looks up a dot separated path like metadata.name or items.0.name, where
+/// numbers index into arrays. A leading dot is optional.
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.strip_prefix('.').unwrap_or(path);
+    if path.is_empty() {
+        return Some(value);
+    }
+    path.split('.')
+        .try_fold(value, |value, segment| match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(array) => array.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
+
+/// Writes one line per leaf path of the given JSON documents.
+pub fn write_paths<W: Write>(values: &[String], writer: &mut W) -> anyhow::Result<()> {
+    for value in values {
+        let value: Value = serde_json::from_str(value)?;
+        for (path, leaf) in leaf_paths(&value) {
+            writeln!(writer, "{path} = {leaf}")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use serde_json::json;
+
+    use super::{lookup, write_paths};
+
+    #[test]
+    fn nested_object() -> Result<(), Box<dyn Error>> {
+        let json = r#"{"a":{"b":[{"c":1},"d"]},"e f":null,"g":{"h i":[]}}"#;
+        let mut buf = Vec::<u8>::new();
+        write_paths(&[json.to_owned()], &mut buf)?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            ".a.b[0].c = 1\n.a.b[1] = \"d\"\n.[\"e f\"] = null\n.g[\"h i\"] = []\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scalar_and_array_root() -> Result<(), Box<dyn Error>> {
+        let mut buf = Vec::<u8>::new();
+        write_paths(&["true".to_owned(), "[1]".to_owned()], &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, ". = true\n.[0] = 1\n");
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_path() {
+        let value = json!({"a": {"b": [{"c": null}]}});
+        assert_eq!(lookup(&value, "a.b.0.c"), Some(&json!(null)));
+        assert_eq!(lookup(&value, ".a.b.0"), Some(&json!({"c": null})));
+        assert_eq!(lookup(&value, "."), Some(&value));
+        assert_eq!(lookup(&value, "a.b.1"), None);
+        assert_eq!(lookup(&value, "a.b.x"), None);
+        assert_eq!(lookup(&value, "a.c"), None);
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::{Read, Seek, Write};
+
+use base64::Engine;
+use serde_json::Value;
+
+/// Reads a xml or binary property list. Dates become their ISO 8601 string
+/// representation, data becomes a base64 encoded string and uids become
+/// integers.
+pub fn read_to_json<R: Read + Seek>(reader: R) -> anyhow::Result<Value> {
+    to_json(plist::Value::from_reader(reader)?)
+}
+
+fn to_json(value: plist::Value) -> anyhow::Result<Value> {
+    Ok(match value {
+        plist::Value::Array(array) => Value::Array(
+            array
+                .into_iter()
+                .map(to_json)
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        plist::Value::Dictionary(dict) => Value::Object(
+            dict.into_iter()
+                .map(|(key, value)| Ok((key, to_json(value)?)))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        plist::Value::Boolean(bool) => Value::Bool(bool),
+        plist::Value::Data(data) => {
+            Value::String(base64::engine::general_purpose::STANDARD.encode(data))
+        }
+        plist::Value::Date(date) => Value::String(date.to_xml_format()),
+        plist::Value::Real(real) => serde_json::Number::from_f64(real)
+            .map(Value::Number)
+            .ok_or_else(|| anyhow::anyhow!("plist real {} is not a valid json number", real))?,
+        plist::Value::Integer(int) => match int.as_signed() {
+            Some(signed) => Value::from(signed),
+            None => Value::from(int.as_unsigned().unwrap_or_default()),
+        },
+        plist::Value::String(string) => Value::String(string),
+        plist::Value::Uid(uid) => Value::from(uid.get()),
+        _ => anyhow::bail!("unsupported plist value"),
+    })
+}
+
+fn from_json(value: Value) -> anyhow::Result<plist::Value> {
+    Ok(match value {
+        Value::Null => anyhow::bail!("plist does not support null values"),
+        Value::Bool(bool) => plist::Value::Boolean(bool),
+        Value::Number(number) => {
+            if let Some(signed) = number.as_i64() {
+                plist::Value::from(signed)
+            } else if let Some(unsigned) = number.as_u64() {
+                plist::Value::from(unsigned)
+            } else {
+                plist::Value::Real(number.as_f64().unwrap_or_default())
+            }
+        }
+        Value::String(string) => plist::Value::String(string),
+        Value::Array(array) => plist::Value::Array(
+            array
+                .into_iter()
+                .map(from_json)
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Value::Object(map) => plist::Value::Dictionary(
+            map.into_iter()
+                .map(|(key, value)| Ok((key, from_json(value)?)))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+    })
+}
+
+/// Writes a json value as xml or binary property list.
+/// Strings are never converted back to dates or data.
+pub fn write_json<W: Write>(value: Value, binary: bool, writer: W) -> anyhow::Result<()> {
+    let plist = from_json(value)?;
+    if binary {
+        plist.to_writer_binary(writer)?;
+    } else {
+        plist.to_writer_xml(writer)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Small splitmix64 generator, which is good enough to sample documents
+/// and yields the same sequence for the same seed on every platform.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos());
+        #[allow(clippy::cast_possible_truncation)]
+        Self(nanos as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in 0..bound.
+    pub fn below(&mut self, bound: usize) -> usize {
+        #[allow(clippy::cast_possible_truncation)]
+        let value = (self.next_u64() % bound as u64) as usize;
+        value
+    }
+}
+
+/// Selects up to n items of a stream with reservoir sampling, so only the
+/// selected items are kept while the stream is consumed.
+pub struct Reservoir<T> {
+    n: usize,
+    offered: usize,
+    // items with their stream position
+    selected: Vec<(usize, T)>,
+}
+
+impl<T> Reservoir<T> {
+    pub fn new(n: usize) -> Self {
+        Self {
+            n,
+            offered: 0,
+            selected: Vec::new(),
+        }
+    }
+
+    pub fn offer(&mut self, item: T, rng: &mut Rng) {
+        let idx = self.offered;
+        self.offered += 1;
+        if self.selected.len() < self.n {
+            self.selected.push((idx, item));
+            return;
+        }
+        let slot = rng.below(idx + 1);
+        if slot < self.n {
+            self.selected[slot] = (idx, item);
+        }
+    }
+
+    /// The selected items in stream order.
+    pub fn into_items(mut self) -> Vec<T> {
+        self.selected.sort_unstable_by_key(|(idx, _)| *idx);
+        self.selected.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+/// Randomly permutes the items with a Fisher-Yates shuffle.
+pub fn shuffle<T>(items: &mut [T], rng: &mut Rng) {
+    for idx in (1..items.len()).rev() {
+        items.swap(idx, rng.below(idx + 1));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{shuffle, Reservoir, Rng};
+
+    fn reservoir(len: usize, n: usize, rng: &mut Rng) -> Vec<usize> {
+        let mut reservoir = Reservoir::new(n);
+        for idx in 0..len {
+            reservoir.offer(idx, rng);
+        }
+        reservoir.into_items()
+    }
+
+    #[test]
+    fn deterministic_with_seed() {
+        let first = reservoir(100, 5, &mut Rng::new(42));
+        let second = reservoir(100, 5, &mut Rng::new(42));
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 5);
+        assert!(first.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(reservoir(100, 3, &mut Rng::new(1)), vec![43, 70, 92]);
+    }
+
+    #[test]
+    fn keeps_only_n() {
+        let mut reservoir = Reservoir::new(3);
+        let mut rng = Rng::new(1);
+        for idx in 0..1000 {
+            reservoir.offer(idx, &mut rng);
+            assert!(reservoir.selected.len() <= 3);
+        }
+        assert_eq!(reservoir.into_items().len(), 3);
+    }
+
+    #[test]
+    fn fewer_than_n() {
+        assert_eq!(reservoir(3, 5, &mut Rng::new(1)), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn shuffle_with_seed() {
+        let mut items = [1, 2, 3, 4, 5];
+        shuffle(&mut items, &mut Rng::new(42));
+        assert_eq!(items, [2, 3, 1, 5, 4]);
+        let mut sorted = items;
+        sorted.sort_unstable();
+        assert_eq!(sorted, [1, 2, 3, 4, 5]);
+    }
+}
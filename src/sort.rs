@@ -0,0 +1,223 @@
+use std::cmp::Ordering;
+
+use serde_json::{Number, Value};
+
+/// Order applied when sorting object keys.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyOrder {
+    /// Keys are compared by their bytes.
+    Lexicographic,
+    /// Runs of digits are compared by their numeric value,
+    /// so "2" is ordered before "10".
+    Natural,
+}
+
+impl KeyOrder {
+    fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            KeyOrder::Lexicographic => a.cmp(b),
+            KeyOrder::Natural => natural_cmp(a, b),
+        }
+    }
+}
+
+/// Recursively sorts the keys of all objects in value.
+pub fn sort_keys(value: &mut Value, order: KeyOrder) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = std::mem::take(map).into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| order.compare(a, b));
+            for (key, mut child) in entries {
+                sort_keys(&mut child, order);
+                map.insert(key, child);
+            }
+        }
+        Value::Array(array) => {
+            for child in array {
+                sort_keys(child, order);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Compares values in the order of jq: null, false, true, numbers,
+/// strings, arrays and objects. Objects are compared by their sorted keys
+/// first and then by the values of those keys.
+pub fn compare_values(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => {
+            if let (Some(a), Some(b)) = (integer(a), integer(b)) {
+                return a.cmp(&b);
+            }
+            let (a, b) = (
+                a.as_f64().unwrap_or_default(),
+                b.as_f64().unwrap_or_default(),
+            );
+            a.total_cmp(&b)
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare_values(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Value::Object(a), Value::Object(b)) => {
+            let mut a_keys: Vec<&String> = a.keys().collect();
+            let mut b_keys: Vec<&String> = b.keys().collect();
+            a_keys.sort();
+            b_keys.sort();
+            a_keys.cmp(&b_keys).then_with(|| {
+                a_keys
+                    .iter()
+                    .map(|key| compare_values(&a[*key], &b[*key]))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Widens integers so that they compare exactly, which f64 cannot do
+/// beyond 2^53.
+fn integer(number: &Number) -> Option<i128> {
+    number
+        .as_i64()
+        .map(i128::from)
+        .or_else(|| number.as_u64().map(i128::from))
+}
+
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chunks = chunks(a);
+    let mut b_chunks = chunks(b);
+    loop {
+        let ordering = match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => {
+                let a_digits = a.starts_with(|c: char| c.is_ascii_digit());
+                let b_digits = b.starts_with(|c: char| c.is_ascii_digit());
+                if a_digits && b_digits {
+                    let a_trimmed = a.trim_start_matches('0');
+                    let b_trimmed = b.trim_start_matches('0');
+                    a_trimmed
+                        .len()
+                        .cmp(&b_trimmed.len())
+                        .then_with(|| a_trimmed.cmp(b_trimmed))
+                        .then_with(|| a.len().cmp(&b.len()))
+                } else {
+                    a.cmp(b)
+                }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// Splits text into alternating runs of digits and non-digits.
+fn chunks(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let digits = first.is_ascii_digit();
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != digits)
+            .unwrap_or(rest.len());
+        let (chunk, remainder) = rest.split_at(end);
+        rest = remainder;
+        Some(chunk)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use std::cmp::Ordering;
+
+    use super::{compare_values, sort_keys, KeyOrder};
+
+    fn keys(value: &Value) -> Vec<&str> {
+        value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn natural_numeric_keys() {
+        let mut value = json!({"10": 0, "2": 0, "1": 0, "b": {"y": 0, "x": 0}, "a10": 0, "a9": 0});
+        sort_keys(&mut value, KeyOrder::Natural);
+        assert_eq!(keys(&value), vec!["1", "2", "10", "a9", "a10", "b"]);
+        assert_eq!(keys(&value["b"]), vec!["x", "y"]);
+    }
+
+    #[test]
+    fn jq_value_order() {
+        let mut values = vec![
+            json!({"a": 1}),
+            json!("b"),
+            json!([1, 2]),
+            json!(10),
+            json!(true),
+            json!(null),
+            json!([1]),
+            json!(2.5),
+            json!(false),
+        ];
+        values.sort_by(compare_values);
+        assert_eq!(
+            values,
+            vec![
+                json!(null),
+                json!(false),
+                json!(true),
+                json!(2.5),
+                json!(10),
+                json!("b"),
+                json!([1]),
+                json!([1, 2]),
+                json!({"a": 1}),
+            ]
+        );
+        assert_eq!(
+            compare_values(&json!({"a": 2}), &json!({"b": 1})),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn exact_integer_order() {
+        assert_eq!(
+            compare_values(
+                &json!(9_007_199_254_740_993_u64),
+                &json!(9_007_199_254_740_992_u64)
+            ),
+            Ordering::Greater
+        );
+        assert_eq!(compare_values(&json!(-1), &json!(u64::MAX)), Ordering::Less);
+        assert_eq!(
+            compare_values(&json!(u64::MAX - 1), &json!(u64::MAX)),
+            Ordering::Less
+        );
+        assert_eq!(compare_values(&json!(2), &json!(2.5)), Ordering::Less);
+    }
+}
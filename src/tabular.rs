@@ -0,0 +1,121 @@
+use std::io::{Read, Write};
+
+use serde_json::{Map, Value};
+
+/// Reads csv with a header row into an array of objects keyed by the
+/// header. All fields are read as strings.
+pub fn read<R: Read>(reader: R, delimiter: u8) -> anyhow::Result<Value> {
+    let mut csv = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(reader);
+    let header = csv.headers()?.clone();
+    let mut rows = Vec::new();
+    for record in csv.records() {
+        let row = header
+            .iter()
+            .zip(record?.iter())
+            .map(|(key, field)| (key.to_owned(), Value::String(field.to_owned())))
+            .collect();
+        rows.push(Value::Object(row));
+    }
+    Ok(Value::Array(rows))
+}
+
+/// Collects the rows of csv output. Every value is either a single object
+/// or an array of objects.
+pub fn rows(values: Vec<Value>) -> anyhow::Result<Vec<Map<String, Value>>> {
+    let mut rows = Vec::new();
+    for value in values {
+        let elements = match value {
+            Value::Array(array) => array,
+            other => vec![other],
+        };
+        for element in elements {
+            match element {
+                Value::Object(map) => rows.push(map),
+                _ => anyhow::bail!("csv output requires objects or arrays of objects"),
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Writes rows with a header, which is the union of the keys of all rows
+/// in order of appearance. Fields missing in a row are left empty.
+pub fn write_rows<W: Write>(
+    rows: &[Map<String, Value>],
+    delimiter: u8,
+    writer: W,
+) -> anyhow::Result<()> {
+    let mut header = Vec::<&str>::new();
+    for row in rows {
+        for key in row.keys() {
+            if !header.contains(&key.as_str()) {
+                header.push(key);
+            }
+        }
+    }
+    let mut csv = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(writer);
+    if header.is_empty() {
+        return Ok(());
+    }
+    csv.write_record(&header)?;
+    for row in rows {
+        let mut record = Vec::with_capacity(header.len());
+        for key in &header {
+            record.push(cell(key, row.get(*key))?);
+        }
+        csv.write_record(&record)?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+fn cell(key: &str, value: Option<&Value>) -> anyhow::Result<String> {
+    Ok(match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(string)) => string.clone(),
+        Some(Value::Array(array)) if array.is_empty() => String::new(),
+        Some(Value::Object(map)) if map.is_empty() => String::new(),
+        Some(Value::Array(_) | Value::Object(_)) => {
+            anyhow::bail!("csv output does not support nested values for key {}", key)
+        }
+        Some(scalar) => scalar.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use serde_json::json;
+
+    use super::{read, rows, write_rows};
+
+    #[test]
+    fn union_header() -> Result<(), Box<dyn Error>> {
+        let rows = rows(vec![json!([{"a": 1, "b": "x, y"}, {"c": true, "a": null}])])?;
+        let mut buf = Vec::<u8>::new();
+        write_rows(&rows, b',', &mut buf)?;
+        assert_eq!(String::from_utf8(buf)?, "a,b,c\n1,\"x, y\",\n,,true\n");
+        Ok(())
+    }
+
+    #[test]
+    fn read_header() -> Result<(), Box<dyn Error>> {
+        let value = read("a,b\n1,\"x, y\"\n".as_bytes(), b',')?;
+        assert_eq!(value, json!([{"a": "1", "b": "x, y"}]));
+        assert!(read("a,b\n1\n".as_bytes(), b',').is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn nested() -> Result<(), Box<dyn Error>> {
+        let rows = rows(vec![json!({"a": {"b": 1}})])?;
+        assert!(write_rows(&rows, b',', Vec::<u8>::new()).is_err());
+        assert!(super::rows(vec![json!([1])]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use toml_edit::visit_mut::VisitMut;
+
+/// Layout of toml output beyond the pretty policy of `toml::Serializer`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Style {
+    /// Writes arrays with more than one element on multiple lines.
+    pub multiline_arrays: bool,
+    /// Spaces before every entry of a multi-line array.
+    pub indent: usize,
+    /// Pads keys, so the equal signs of a table line up.
+    pub align: bool,
+    /// Writes all strings as basic strings, while the serializer prefers
+    /// literal strings for text with backslashes.
+    pub basic_strings: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            multiline_arrays: false,
+            indent: 4,
+            align: false,
+            basic_strings: false,
+        }
+    }
+}
+
+/// Re-formats a toml document serialized by `toml::Serializer`.
+pub fn format(toml: &str, mut style: Style) -> anyhow::Result<String> {
+    let mut document: toml_edit::DocumentMut = toml.parse()?;
+    style.visit_document_mut(&mut document);
+    Ok(document.to_string())
+}
+
+impl VisitMut for Style {
+    fn visit_table_mut(&mut self, node: &mut toml_edit::Table) {
+        if self.align {
+            let width = node
+                .iter_mut()
+                .filter(|(_, item)| item.is_value())
+                .map(|(key, _)| key.display_repr().len())
+                .max()
+                .unwrap_or_default();
+            for (mut key, item) in node.iter_mut() {
+                if item.is_value() {
+                    let pad = width - key.display_repr().len();
+                    key.leaf_decor_mut().set_suffix(" ".repeat(pad + 1));
+                }
+            }
+        }
+        toml_edit::visit_mut::visit_table_mut(self, node);
+    }
+
+    fn visit_value_mut(&mut self, node: &mut toml_edit::Value) {
+        if self.basic_strings && node.is_str() && node.to_string().trim().starts_with('\'') {
+            let decor = node.decor().clone();
+            // json escapes are valid in toml basic strings
+            if let Some(basic) = node
+                .as_str()
+                .and_then(|text| serde_json::to_string(text).ok())
+                .and_then(|basic| basic.parse().ok())
+            {
+                *node = basic;
+                *node.decor_mut() = decor;
+            }
+        }
+        toml_edit::visit_mut::visit_value_mut(self, node);
+    }
+
+    fn visit_array_mut(&mut self, node: &mut toml_edit::Array) {
+        toml_edit::visit_mut::visit_array_mut(self, node);
+        if !self.multiline_arrays || node.len() <= 1 {
+            return;
+        }
+        let prefix = format!("\n{}", " ".repeat(self.indent));
+        for item in node.iter_mut() {
+            item.decor_mut().set_prefix(prefix.clone());
+        }
+        node.set_trailing("\n");
+        node.set_trailing_comma(true);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use super::{format, Style};
+
+    const TOML: &str = "a = [1, 2]\nlong_key = \"x\"\n";
+
+    #[test]
+    fn indent_entries() -> Result<(), Box<dyn Error>> {
+        let style = Style {
+            multiline_arrays: true,
+            indent: 2,
+            align: false,
+            basic_strings: false,
+        };
+        assert_eq!(
+            format(TOML, style)?,
+            "a = [\n  1,\n  2,\n]\nlong_key = \"x\"\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn inline_arrays() -> Result<(), Box<dyn Error>> {
+        assert_eq!(format(TOML, Style::default())?, TOML);
+        Ok(())
+    }
+
+    #[test]
+    fn align_entries() -> Result<(), Box<dyn Error>> {
+        let style = Style {
+            align: true,
+            ..Style::default()
+        };
+        assert_eq!(
+            format("a = 1\nlong_key = 2\n\n[t]\nbb = 3\nc = 4\n", style)?,
+            "a        = 1\nlong_key = 2\n\n[t]\nbb = 3\nc  = 4\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn basic_strings() -> Result<(), Box<dyn Error>> {
+        let toml = r#"path = 'C:\Users\nuq'
+lines = '''
+a\b
+c'''
+plain = "x"
+"#;
+        assert_eq!(format(toml, Style::default())?, toml);
+        let style = Style {
+            basic_strings: true,
+            ..Style::default()
+        };
+        assert_eq!(
+            format(toml, style)?,
+            r#"path = "C:\\Users\\nuq"
+lines = "a\\b\nc"
+plain = "x"
+"#
+        );
+        Ok(())
+    }
+}
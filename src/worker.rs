@@ -0,0 +1,136 @@
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    time::Duration,
+};
+
+/// Runs a jq program on a dedicated thread, so a run can be given up on
+/// after a timeout. jq can not be interrupted during a run, so the timeout
+/// is best effort: a timed out run is abandoned and keeps its thread busy
+/// until it finishes or the process exits. Later runs use a new thread, so
+/// the late result of an abandoned run is never taken for theirs.
+pub struct Worker {
+    program: String,
+    requests: Sender<String>,
+    responses: Receiver<Result<String, String>>,
+    timeout: Duration,
+}
+
+impl Worker {
+    pub fn spawn(program: &str, timeout: Duration) -> anyhow::Result<Worker> {
+        let (request_tx, request_rx) = mpsc::channel::<String>();
+        let (response_tx, response_rx) = mpsc::channel();
+        let source = program.to_owned();
+        // the compiled program is not Send, so it is compiled on the worker
+        std::thread::spawn(move || {
+            let mut program = match jq_rs::compile(&source) {
+                Ok(program) => {
+                    let _ = response_tx.send(Ok(String::new()));
+                    program
+                }
+                Err(err) => {
+                    let _ = response_tx.send(Err(err.to_string()));
+                    return;
+                }
+            };
+            for json in request_rx {
+                let output = program.run(&json).map_err(|err| err.to_string());
+                if response_tx.send(output).is_err() {
+                    return;
+                }
+            }
+        });
+        response_rx
+            .recv()?
+            .map_err(|err| anyhow::anyhow!("{}", err))?;
+        Ok(Self {
+            program: program.to_owned(),
+            requests: request_tx,
+            responses: response_rx,
+            timeout,
+        })
+    }
+
+    pub fn run(&mut self, json: &str) -> anyhow::Result<String> {
+        self.requests.send(json.to_owned())?;
+        match self.responses.recv_timeout(self.timeout) {
+            Ok(output) => output.map_err(|err| anyhow::anyhow!("{}", err)),
+            Err(RecvTimeoutError::Timeout) => {
+                // the abandoned run would answer the next request otherwise
+                *self = Worker::spawn(&self.program, self.timeout)?;
+                anyhow::bail!("jq program timed out after {:?}", self.timeout)
+            }
+            Err(RecvTimeoutError::Disconnected) => anyhow::bail!("jq worker stopped unexpectedly"),
+        }
+    }
+}
+
+/// Parses a duration like 500ms, 10s or 2m. A plain number is read as seconds.
+pub fn parse_duration(input: &str) -> anyhow::Result<Duration> {
+    let split = input
+        .find(|char: char| !char.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid duration: {}", input))?;
+    Ok(match unit {
+        "ms" => Duration::from_millis(amount),
+        "" | "s" => Duration::from_secs(amount),
+        "m" => Duration::from_secs(
+            amount
+                .checked_mul(60)
+                .ok_or_else(|| anyhow::anyhow!("duration too long: {}", input))?,
+        ),
+        _ => anyhow::bail!("invalid duration unit in {}, expected ms, s or m", input),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{error::Error, time::Duration};
+
+    use super::{parse_duration, Worker};
+
+    #[test]
+    fn durations() -> Result<(), Box<dyn Error>> {
+        assert_eq!(parse_duration("250ms")?, Duration::from_millis(250));
+        assert_eq!(parse_duration("3")?, Duration::from_secs(3));
+        assert_eq!(parse_duration("2m")?.as_secs(), 120);
+        assert!(parse_duration("1h").is_err());
+        assert!(parse_duration("ms").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn fast_program() -> Result<(), Box<dyn Error>> {
+        let mut worker = Worker::spawn(".a", Duration::from_secs(10))?;
+        assert_eq!(worker.run(r#"{"a":1}"#)?, "1\n");
+        assert_eq!(worker.run(r#"{"a":2}"#)?, "2\n");
+        Ok(())
+    }
+
+    #[test]
+    fn slow_program() -> Result<(), Box<dyn Error>> {
+        // finishes shortly after the timeout, so the abandoned thread does
+        // not keep spinning for the rest of the test run
+        let mut worker = Worker::spawn("last(range(1e6))", Duration::from_millis(50))?;
+        let err = worker.run("null").unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+        Ok(())
+    }
+
+    #[test]
+    fn run_after_timeout() -> Result<(), Box<dyn Error>> {
+        let program = "if . == 1 then (last(range(1e6)) | 1000) else . * 10 end";
+        let mut worker = Worker::spawn(program, Duration::from_millis(50))?;
+        assert!(worker.run("1").is_err());
+        assert_eq!(worker.run("2")?, "20\n");
+        assert_eq!(worker.run("3")?, "30\n");
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_program() {
+        assert!(Worker::spawn(".[", Duration::from_secs(1)).is_err());
+    }
+}
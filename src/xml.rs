@@ -0,0 +1,160 @@
+use std::io::BufRead;
+
+use quick_xml::{
+    escape::resolve_predefined_entity,
+    events::{BytesStart, Event},
+    Reader, XmlVersion,
+};
+use serde_json::{Map, Value};
+
+/// Reads an xml document into an object with the root element as only key.
+/// Attributes become fields prefixed with `@`. An element with only text
+/// becomes a string and an empty element null. Text next to attributes or
+/// child elements is kept in the `#text` field. Repeated child elements
+/// are collected into an array in document order.
+pub fn read_to_json<R: BufRead>(reader: R) -> anyhow::Result<Value> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = Vec::new();
+    // elements, which are opened but not closed yet
+    let mut stack = Vec::<Element>::new();
+    let mut root = None;
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(start) => stack.push(Element::new(&start)?),
+            Event::Empty(start) => close(Element::new(&start)?, &mut stack, &mut root)?,
+            Event::End(_) => {
+                let element = stack
+                    .pop()
+                    .ok_or_else(|| anyhow::anyhow!("unexpected closing tag"))?;
+                close(element, &mut stack, &mut root)?;
+            }
+            Event::Text(text) => push_text(&mut stack, &text.xml10_content())?,
+            Event::CData(data) => push_text(&mut stack, &data.xml10_content())?,
+            Event::GeneralRef(reference) => {
+                let resolved = match reference.resolve_char_ref()? {
+                    Some(char) => char.to_string(),
+                    None => resolve_predefined_entity(&reference)
+                        .ok_or_else(|| anyhow::anyhow!("unknown entity &{};", &*reference))?
+                        .to_owned(),
+                };
+                push_text(&mut stack, &resolved)?;
+            }
+            Event::Eof => break,
+            // declarations, comments, processing instructions and doctypes
+            _ => {}
+        }
+        buf.clear();
+    }
+    if let Some(element) = stack.last() {
+        anyhow::bail!("element {} is not closed", element.name);
+    }
+    root.ok_or_else(|| anyhow::anyhow!("xml document has no root element"))
+}
+
+struct Element {
+    name: String,
+    fields: Map<String, Value>,
+    text: String,
+}
+
+impl Element {
+    fn new(start: &BytesStart) -> anyhow::Result<Self> {
+        let mut fields = Map::new();
+        for attribute in start.attributes() {
+            let attribute = attribute?;
+            let value = attribute.normalized_value(XmlVersion::Implicit1_0)?;
+            fields.insert(
+                format!("@{}", attribute.key.as_ref()),
+                Value::String(value.into_owned()),
+            );
+        }
+        Ok(Self {
+            name: start.name().as_ref().to_owned(),
+            fields,
+            text: String::new(),
+        })
+    }
+
+    fn into_value(self) -> Value {
+        let text = self.text.trim();
+        if self.fields.is_empty() {
+            if text.is_empty() {
+                return Value::Null;
+            }
+            return Value::String(text.to_owned());
+        }
+        let mut fields = self.fields;
+        if !text.is_empty() {
+            fields.insert("#text".to_owned(), Value::String(text.to_owned()));
+        }
+        Value::Object(fields)
+    }
+}
+
+fn push_text(stack: &mut [Element], text: &str) -> anyhow::Result<()> {
+    match stack.last_mut() {
+        Some(element) => element.text.push_str(text),
+        None if text.trim().is_empty() => {}
+        None => anyhow::bail!("text outside of the root element"),
+    }
+    Ok(())
+}
+
+fn close(element: Element, stack: &mut [Element], root: &mut Option<Value>) -> anyhow::Result<()> {
+    let name = element.name.clone();
+    let value = element.into_value();
+    let Some(parent) = stack.last_mut() else {
+        if root.is_some() {
+            anyhow::bail!("xml document has more than one root element");
+        }
+        *root = Some(Value::Object(Map::from_iter([(name, value)])));
+        return Ok(());
+    };
+    // element values are never arrays, so an array marks a repeated element
+    match parent.fields.get_mut(&name) {
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+        None => {
+            parent.fields.insert(name, value);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use serde_json::json;
+
+    use super::read_to_json;
+
+    #[test]
+    fn attributes_and_text() -> Result<(), Box<dyn Error>> {
+        let xml = r#"<?xml version="1.0"?>
+<config version="2">
+  <!-- servers -->
+  <server name="a">one &amp; only</server>
+  <server name="b"/>
+  <port>80</port>
+  <empty/>
+</config>"#;
+        assert_eq!(
+            read_to_json(xml.as_bytes())?,
+            json!({"config": {
+                "@version": "2",
+                "server": [{"@name": "a", "#text": "one & only"}, {"@name": "b"}],
+                "port": "80",
+                "empty": null,
+            }})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(read_to_json("a: b".as_bytes()).is_err());
+        assert!(read_to_json("<a><b></a>".as_bytes()).is_err());
+        assert!(read_to_json("<a/><b/>".as_bytes()).is_err());
+    }
+}
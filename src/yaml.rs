@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use serde_json::Value;
+
+/// How scalar strings are quoted in yaml output. Auto leaves the decision
+/// to `serde_yaml`. Plain falls back to double quotes for strings, which
+/// would not be read back as the same string otherwise.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum QuoteStyle {
+    #[default]
+    Auto,
+    Single,
+    Double,
+    Plain,
+}
+
+/// Block style yaml emitter, which mimics the layout of `serde_yaml`,
+/// but gives control over the quoting of strings and anchors.
+pub struct Emitter {
+    quote: QuoteStyle,
+    anchors: bool,
+    align: bool,
+}
+
+impl Emitter {
+    pub fn new(quote: QuoteStyle) -> Self {
+        Self {
+            quote,
+            anchors: false,
+            align: false,
+        }
+    }
+
+    /// Aligns the scalar values of every mapping at a common column after
+    /// its longest key.
+    pub fn with_alignment(mut self, align: bool) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Writes repeated identical objects and arrays only once with an
+    /// anchor and refers to them with aliases afterwards.
+    pub fn with_anchors(mut self, anchors: bool) -> Self {
+        self.anchors = anchors;
+        self
+    }
+
+    pub fn emit(&self, value: &Value) -> String {
+        let mut out = String::new();
+        let mut anchors = Anchors::default();
+        if self.anchors {
+            anchors.count(value);
+        }
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                self.emit_map(map, 0, &mut anchors, &mut out);
+            }
+            Value::Array(array) if !array.is_empty() => {
+                self.emit_seq(array, 0, &mut anchors, &mut out);
+            }
+            _ => {
+                out.push_str(&self.scalar(value));
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    fn emit_map(
+        &self,
+        map: &serde_json::Map<String, Value>,
+        indent: usize,
+        anchors: &mut Anchors,
+        out: &mut String,
+    ) {
+        let pad = " ".repeat(indent);
+        let width = if self.align {
+            map.iter()
+                .filter(|(_, value)| !is_collection(value))
+                .map(|(key, _)| self.key(key).chars().count())
+                .max()
+                .unwrap_or_default()
+        } else {
+            0
+        };
+        for (key, value) in map {
+            let key = self.key(key);
+            out.push_str(&pad);
+            out.push_str(&key);
+            out.push(':');
+            if !is_collection(value) {
+                out.push_str(&" ".repeat(width.saturating_sub(key.chars().count()) + 1));
+                out.push_str(&self.scalar(value));
+                out.push('\n');
+                continue;
+            }
+            match anchors.mark(value) {
+                Mark::Alias(alias) => {
+                    out.push_str(" *");
+                    out.push_str(&alias);
+                    out.push('\n');
+                    continue;
+                }
+                Mark::Anchor(anchor) => {
+                    out.push_str(" &");
+                    out.push_str(&anchor);
+                }
+                Mark::None => {}
+            }
+            out.push('\n');
+            match value {
+                Value::Object(child) => self.emit_map(child, indent + 2, anchors, out),
+                Value::Array(child) => self.emit_seq(child, indent, anchors, out),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    fn emit_seq(&self, array: &[Value], indent: usize, anchors: &mut Anchors, out: &mut String) {
+        let pad = " ".repeat(indent);
+        for value in array {
+            out.push_str(&pad);
+            out.push_str("- ");
+            if !is_collection(value) {
+                out.push_str(&self.scalar(value));
+                out.push('\n');
+                continue;
+            }
+            let mark = anchors.mark(value);
+            if let Mark::Alias(alias) = mark {
+                out.push('*');
+                out.push_str(&alias);
+                out.push('\n');
+                continue;
+            }
+            let mut nested = String::new();
+            match value {
+                Value::Object(child) => self.emit_map(child, indent + 2, anchors, &mut nested),
+                Value::Array(child) => self.emit_seq(child, indent + 2, anchors, &mut nested),
+                _ => unreachable!(),
+            }
+            if let Mark::Anchor(anchor) = mark {
+                out.push('&');
+                out.push_str(&anchor);
+                out.push('\n');
+                out.push_str(&nested);
+            } else {
+                // the first nested line continues after the dash
+                out.push_str(&nested[indent + 2..]);
+            }
+        }
+    }
+
+    fn scalar(&self, value: &Value) -> String {
+        match value {
+            Value::String(string) => self.string(string),
+            Value::Object(_) => "{}".to_owned(),
+            Value::Array(_) => "[]".to_owned(),
+            _ => value.to_string(),
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        if is_plain_safe(key) {
+            return key.to_owned();
+        }
+        match self.quote {
+            QuoteStyle::Single => single_quoted(key),
+            _ => Value::String(key.to_owned()).to_string(),
+        }
+    }
+
+    fn string(&self, string: &str) -> String {
+        // line breaks and control characters can only be escaped in double quotes
+        let force_double = string.chars().any(char::is_control);
+        match self.quote {
+            QuoteStyle::Single if !force_double => single_quoted(string),
+            QuoteStyle::Plain if !force_double && is_plain_safe(string) => string.to_owned(),
+            // block scalars of serde_yaml are not indented for their position
+            QuoteStyle::Auto if !force_double => serde_yaml::to_string(string).map_or_else(
+                |_| Value::String(string.to_owned()).to_string(),
+                |yaml| yaml.trim_end().to_owned(),
+            ),
+            _ => Value::String(string.to_owned()).to_string(),
+        }
+    }
+}
+
+fn is_collection(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(array) => !array.is_empty(),
+        _ => false,
+    }
+}
+
+enum Mark {
+    None,
+    Anchor(String),
+    Alias(String),
+}
+
+/// Tracks objects and arrays, which occur more than once in a document.
+/// Subtrees of repeated values are only counted once, as later
+/// occurrences are written as aliases.
+#[derive(Default)]
+struct Anchors {
+    counts: HashMap<String, usize>,
+    names: HashMap<String, String>,
+}
+
+impl Anchors {
+    fn count(&mut self, value: &Value) {
+        if !is_collection(value) {
+            return;
+        }
+        let count = self.counts.entry(value.to_string()).or_default();
+        *count += 1;
+        if *count > 1 {
+            return;
+        }
+        match value {
+            Value::Object(map) => map.values().for_each(|child| self.count(child)),
+            Value::Array(array) => array.iter().for_each(|child| self.count(child)),
+            _ => {}
+        }
+    }
+
+    /// Names the first occurrence of a repeated value and returns
+    /// that name for all following ones.
+    fn mark(&mut self, value: &Value) -> Mark {
+        if self.counts.is_empty() {
+            return Mark::None;
+        }
+        let key = value.to_string();
+        if let Some(name) = self.names.get(&key) {
+            return Mark::Alias(name.clone());
+        }
+        if self.counts.get(&key).copied().unwrap_or_default() < 2 {
+            return Mark::None;
+        }
+        let name = format!("a{}", self.names.len() + 1);
+        self.names.insert(key, name.clone());
+        Mark::Anchor(name)
+    }
+}
+
+fn single_quoted(string: &str) -> String {
+    format!("'{}'", string.replace('\'', "''"))
+}
+
+/// A string can be written without quotes, if reading it back
+/// yields the same string. Yaml 1.1 booleans like yes or on are quoted
+/// too, as older parsers still read them as booleans.
+fn is_plain_safe(string: &str) -> bool {
+    const YAML_1_1_BOOLS: [&str; 8] = ["y", "n", "yes", "no", "on", "off", "true", "false"];
+    if YAML_1_1_BOOLS.contains(&string.to_ascii_lowercase().as_str()) {
+        return false;
+    }
+    matches!(
+        serde_yaml::from_str::<Value>(string),
+        Ok(Value::String(parsed)) if parsed == string
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{Emitter, QuoteStyle};
+
+    #[test]
+    fn single() {
+        let emitter = Emitter::new(QuoteStyle::Single);
+        assert_eq!(emitter.emit(&json!({"a": "it's"})), "a: 'it''s'\n");
+    }
+
+    #[test]
+    fn double() {
+        let emitter = Emitter::new(QuoteStyle::Double);
+        assert_eq!(emitter.emit(&json!({"a": "b"})), "a: \"b\"\n");
+    }
+
+    #[test]
+    fn plain() {
+        let emitter = Emitter::new(QuoteStyle::Plain);
+        assert_eq!(emitter.emit(&json!({"a": "b"})), "a: b\n");
+        assert_eq!(emitter.emit(&json!({"a": "true"})), "a: \"true\"\n");
+        assert_eq!(emitter.emit(&json!({"on": "Yes"})), "\"on\": \"Yes\"\n");
+    }
+
+    #[test]
+    fn auto() {
+        let emitter = Emitter::new(QuoteStyle::Auto);
+        assert_eq!(emitter.emit(&json!({"a": "b", "c": "1"})), "a: b\nc: '1'\n");
+    }
+
+    #[test]
+    fn auto_multi_line() {
+        let value = json!({"a": {"b": "x\ny"}, "c": ["d\ne\n"]});
+        let yaml = Emitter::new(QuoteStyle::Auto).emit(&value);
+        assert_eq!(yaml, "a:\n  b: \"x\\ny\"\nc:\n- \"d\\ne\\n\"\n");
+        assert_eq!(
+            serde_yaml::from_str::<serde_json::Value>(&yaml).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn nested_layout() {
+        let value = json!({"a": [{"b": 1, "c": [2, [3, 4]]}], "d": {"e": {}}});
+        let emitter = Emitter::new(QuoteStyle::Double);
+        let yaml = emitter.emit(&value);
+        assert_eq!(yaml, serde_yaml::to_string(&value).unwrap());
+    }
+
+    #[test]
+    fn aligned() {
+        let value = json!({
+            "a": 1,
+            "long": {"bb": true, "c": [{"ddd": 2, "e": "f\ng"}]},
+            "gg": null,
+        });
+        let yaml = Emitter::new(QuoteStyle::Auto)
+            .with_alignment(true)
+            .emit(&value);
+        assert_eq!(
+            yaml,
+            "a:  1\nlong:\n  bb: true\n  c:\n  - ddd: 2\n    e:   \"f\\ng\"\ngg: null\n"
+        );
+        assert_eq!(
+            serde_yaml::from_str::<serde_json::Value>(&yaml).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn anchors() {
+        let value = json!({
+            "base": {"x": 1, "z": [1, 2]},
+            "dev": {"x": 1, "z": [1, 2]},
+            "list": [{"x": 1, "z": [1, 2]}, [1, 2]],
+        });
+        let emitter = Emitter::new(QuoteStyle::Auto).with_anchors(true);
+        let yaml = emitter.emit(&value);
+        assert_eq!(
+            yaml,
+            "base: &a1\n  x: 1\n  z: &a2\n  - 1\n  - 2\ndev: *a1\nlist:\n- *a1\n- *a2\n"
+        );
+        assert_eq!(
+            serde_yaml::from_str::<serde_json::Value>(&yaml).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn anchored_sequence_item() {
+        let value = json!([[{"a": 1}], [{"a": 1}]]);
+        let yaml = Emitter::new(QuoteStyle::Auto)
+            .with_anchors(true)
+            .emit(&value);
+        assert_eq!(yaml, "- &a1\n  - a: 1\n- *a1\n");
+        assert_eq!(
+            serde_yaml::from_str::<serde_json::Value>(&yaml).unwrap(),
+            value
+        );
+    }
+}
@@ -35,11 +35,624 @@ fn slurp() {
     );
 }
 
+#[test]
+fn many_files() {
+    let dir = std::env::temp_dir().join("nuq-many-files");
+    std::fs::create_dir_all(&dir).expect("failed to create temp dir");
+    let mut args = vec![".key".to_owned()];
+    for idx in 0..100 {
+        let path = dir.join(format!("mock{idx}.yaml"));
+        std::fs::write(&path, format!("key: {idx}")).expect("failed to create mock yaml");
+        args.push(path.to_string_lossy().into_owned());
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (exit, output) = spawn_nuq(&args, b"");
+    std::fs::remove_dir_all(&dir).expect("failed to remove temp dir");
+    assert!(exit.success());
+    let lines: Vec<String> = (0..100).map(|idx| idx.to_string()).collect();
+    assert_eq!(output, lines.join("\n") + "\n");
+}
+
 #[test]
 fn yaml_stdin_identity_color() {
     let (exit, output) = spawn_nuq(&["-i", "yaml", "-c", "true", "."], b"key: test");
     assert!(exit.success());
-    assert_eq!(output, "\u{1b}[38;2;191;97;106mkey\u{1b}[38;2;192;197;206m:\u{1b}[38;2;192;197;206m \u{1b}[38;2;163;190;140mtest\u{1b}[38;2;192;197;206m\n\u{1b}[0m");
+    assert_eq!(output, "\u{1b}[38;2;191;97;106mkey\u{1b}[38;2;192;197;206m:\u{1b}[38;2;192;197;206m \u{1b}[38;2;163;190;140mtest\u{1b}[38;2;192;197;206m\u{1b}[0m\n");
+}
+
+#[test]
+fn pretty_ron_color() {
+    let (exit, output) = spawn_nuq(&["-o", "ron", "-p", "-c", "true", "."], br#"{"a":[1]}"#);
+    assert!(exit.success());
+    assert_eq!(output, "{\n    \"a\": [\n        1,\n    ],\n}\n");
+}
+
+#[test]
+fn raw_json_color() {
+    let (exit, output) = spawn_nuq(&["-r", "-c", "true", ".a"], br#"{"a":{"b":1}}"#);
+    assert!(exit.success());
+    assert!(output.starts_with("\u{1b}["));
+    let (exit, output) = spawn_nuq(&["-r", "-c", "true", ".a"], br#"{"a":"text"}"#);
+    assert!(exit.success());
+    assert_eq!(output, "text\n");
+}
+
+#[test]
+fn tee_is_not_colored() {
+    let (exit, output) = spawn_nuq(
+        &["-i", "yaml", "-c", "true", "--tee", "mock-tee.yaml", "."],
+        b"key: test",
+    );
+    let teed = std::fs::read_to_string("./mock-tee.yaml").expect("failed to read mock-tee.yaml");
+    std::fs::remove_file("./mock-tee.yaml").expect("failed to remove mock-tee.yaml");
+    assert!(exit.success());
+    assert!(output.contains('\u{1b}'));
+    assert_eq!(teed, "key: test\n");
+}
+
+#[test]
+fn strip_ansi() {
+    let colored = b"\x1b[38;2;192;197;206m{\x1b[38;2;191;97;106m\"key\"\x1b[38;2;192;197;206m:\"test\"}\n\x1b[0m";
+    let (exit, output) = spawn_nuq(&["--strip-ansi", "-r", ".key"], colored);
+    assert!(exit.success());
+    assert_eq!(output, "test\n");
+}
+
+#[test]
+fn color_diff() {
+    let args = ["-i", "yaml", "--color-diff", ".key = \"new\""];
+    let (exit, colored) = spawn_nuq(&[&["-c", "true"], &args[..]].concat(), b"key: old");
+    assert!(exit.success());
+    assert!(colored.contains("\u{1b}[31m-key: old\u{1b}[0m"));
+    assert!(colored.contains("\u{1b}[32m+key: new\u{1b}[0m"));
+    let (exit, plain) = spawn_nuq(&[&["-c", "false"], &args[..]].concat(), b"key: old");
+    assert!(exit.success());
+    assert!(!plain.contains('\u{1b}'));
+    assert!(plain.contains("-key: old\n+key: new\n"));
+}
+
+#[test]
+fn type_filter() {
+    let input = br#"{"a":1} [1] "s" 2 null {"b":2} true"#;
+    let (exit, output) = spawn_nuq(&["--type", "object", "."], input);
+    assert!(exit.success());
+    assert_eq!(output, "{\"a\":1}\n{\"b\":2}\n");
+    let (exit, output) = spawn_nuq(&["--type", "number", "--head", "1", "."], input);
+    assert!(exit.success());
+    assert_eq!(output, "2\n");
+}
+
+#[test]
+fn concat_raw() {
+    std::fs::write("./mock-concat1.json", r#"["a","b"]"#).expect("failed to create mock json");
+    std::fs::write("./mock-concat2.json", r#"["c"]"#).expect("failed to create mock json");
+    let args = [
+        "-r",
+        "--output-separator",
+        ",",
+        ".[]",
+        "mock-concat1.json",
+        "mock-concat2.json",
+    ];
+    let (_, separate) = spawn_nuq(&args, b"");
+    let (exit, concatenated) = spawn_nuq(&[&args[..], &["--concat-raw"]].concat(), b"");
+    std::fs::remove_file("./mock-concat1.json").expect("failed to remove mock json");
+    std::fs::remove_file("./mock-concat2.json").expect("failed to remove mock json");
+    assert!(exit.success());
+    assert_eq!(separate, "a,b\nc\n");
+    assert_eq!(concatenated, "a,b,c\n");
+}
+
+#[test]
+fn unbuffered() {
+    std::fs::write("./mock-fast.json", r#"{"slow":false}"#).expect("failed to create mock json");
+    std::fs::write("./mock-slow.json", r#"{"slow":true}"#).expect("failed to create mock json");
+    let mut handle = Command::new(BINARY_PATH)
+        .args([
+            "--unbuffered",
+            "--timeout",
+            "10s",
+            "if .slow then last(range(1e12)) else .slow end",
+            "mock-fast.json",
+            "mock-slow.json",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to launch nuq process.");
+    let mut stdout = handle.stdout.take().unwrap();
+    let mut line = [0u8; 6];
+    stdout
+        .read_exact(&mut line)
+        .expect("Failed to read nuq output.");
+    // the first output arrives while the second input is still processed
+    let running = handle.try_wait().expect("Failed to poll nuq.").is_none();
+    handle.kill().expect("Failed to kill nuq.");
+    std::fs::remove_file("./mock-fast.json").expect("failed to remove mock json");
+    std::fs::remove_file("./mock-slow.json").expect("failed to remove mock json");
+    assert_eq!(&line, b"false\n");
+    assert!(running);
+}
+
+#[test]
+fn unbuffered_documents() {
+    let mut handle = Command::new(BINARY_PATH)
+        .args([
+            "--unbuffered",
+            "--timeout",
+            "10s",
+            "if .slow then last(range(1e12)) else .slow end",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to launch nuq process.");
+    let mut stdin = handle.stdin.take().unwrap();
+    stdin
+        .write_all(br#"{"slow":false} {"slow":true}"#)
+        .expect("Failed to write to nuq stdin.");
+    drop(stdin);
+    let mut stdout = handle.stdout.take().unwrap();
+    let mut line = [0u8; 6];
+    stdout
+        .read_exact(&mut line)
+        .expect("Failed to read nuq output.");
+    // the first document is written while the second one is processed
+    let running = handle.try_wait().expect("Failed to poll nuq.").is_none();
+    handle.kill().expect("Failed to kill nuq.");
+    assert_eq!(&line, b"false\n");
+    assert!(running);
+    let yaml = b"a: 1\n---\na: 2\n";
+    let (exit, buffered) = spawn_nuq(&["."], yaml);
+    assert!(exit.success());
+    let (exit, unbuffered) = spawn_nuq(&["--unbuffered", "."], yaml);
+    assert!(exit.success());
+    assert_eq!(unbuffered, buffered);
+}
+
+#[test]
+fn stdin_filename() {
+    // guessing reads this as a yaml string
+    let (exit, output) = spawn_nuq(
+        &["--stdin-filename", "x.toml", "-o", "json", ".a"],
+        b"a = 1",
+    );
+    assert!(exit.success());
+    assert_eq!(output, "1\n");
+    let (exit, output) = spawn_nuq(&["--stdin-filename", "x.yaml", ".a"], b"a: [1]");
+    assert!(exit.success());
+    assert_eq!(output, "- 1\n");
+}
+
+#[test]
+fn explode() {
+    let input = br#"[{"a":1},{"b":[2,3]}]"#;
+    let (exit, output) = spawn_nuq(&["--explode", "-o", "yaml", "."], input);
+    assert!(exit.success());
+    assert_eq!(output, "---\na: 1\n---\nb:\n- 2\n- 3\n");
+}
+
+#[test]
+fn no_trailing_newline() {
+    let (exit, output) = spawn_nuq(&["--no-trailing-newline", "-o", "toml", "."], br#"{"a":1}"#);
+    assert!(exit.success());
+    assert_eq!(output, "a = 1");
+}
+
+#[test]
+fn stream_inputs() {
+    std::fs::write("./mock-stream1.json", "{\"a\":1}\n{\"a\":2}\n")
+        .expect("failed to create mock json");
+    std::fs::write("./mock-stream2.json", "{\"a\":").expect("failed to create mock json");
+    let args = [".a", "mock-stream1.json", "mock-stream2.json"];
+    let (exit, output) = spawn_nuq(&args, b"");
+    let (head_exit, head_output) = spawn_nuq(&["--head", "1", ".a", args[1], args[2]], b"");
+    let (tail_exit, _) = spawn_nuq(&["--tail", "1", ".a", args[1], args[2]], b"");
+    std::fs::remove_file("./mock-stream1.json").expect("failed to remove mock json");
+    std::fs::remove_file("./mock-stream2.json").expect("failed to remove mock json");
+    // the first input is written before the second one fails
+    assert!(!exit.success());
+    assert_eq!(output, "1\n2\n");
+    // enough documents are read from the first input
+    assert!(head_exit.success());
+    assert_eq!(head_output, "1\n");
+    // the last documents are only known after reading every input
+    assert!(!tail_exit.success());
+}
+
+#[test]
+fn input_limit() {
+    std::fs::write("./mock-limit1.yaml", "a: 1\n---\na: 2\n---\na: 3\n")
+        .expect("failed to create mock yaml");
+    std::fs::write("./mock-limit2.yaml", "a: 4\n---\na: 5\n").expect("failed to create mock yaml");
+    let args = [
+        "--input-limit",
+        "2",
+        "-o",
+        "json",
+        ".a",
+        "mock-limit1.yaml",
+        "mock-limit2.yaml",
+    ];
+    let (exit, output) = spawn_nuq(&args, b"");
+    std::fs::remove_file("./mock-limit1.yaml").expect("failed to remove mock yaml");
+    std::fs::remove_file("./mock-limit2.yaml").expect("failed to remove mock yaml");
+    assert!(exit.success());
+    assert_eq!(output, "1\n2\n4\n5\n");
+}
+
+#[test]
+fn to_csv() {
+    let input =
+        br#"{"records":[{"id":1,"name":"a"},{"id":2,"tags":["x"]},{"name":"c","meta":{"k":"v"}}]}"#;
+    let (exit, output) = spawn_nuq(&["--to-csv", ".records"], input);
+    assert!(exit.success());
+    assert_eq!(output, "id,name,tags.0,meta.k\n1,a,,\n2,,x,\n,c,,v\n");
+}
+
+#[test]
+fn guess_yaml_before_cson() {
+    let input = b"enabled: yes\nport: 80\nhosts: [a, b]\n";
+    let (exit, output) = spawn_nuq(&["-o", "json", "."], input);
+    assert!(exit.success());
+    assert_eq!(
+        output,
+        "{\"enabled\":\"yes\",\"port\":80,\"hosts\":[\"a\",\"b\"]}\n"
+    );
+    let (exit, output) = spawn_nuq(&["."], b"a: [1, 2]\nb: \"x\"\nc: no\n");
+    assert!(exit.success());
+    assert_eq!(output, "a:\n- 1\n- 2\nb: x\nc: no\n");
+}
+
+#[test]
+fn multi_format() {
+    let input = b"{\"a\": 1}\n===\nb: 2\nc: [3]\n===\n";
+    let (exit, output) = spawn_nuq(&["--multi-format", "."], input);
+    assert!(exit.success());
+    assert_eq!(output, "{\"a\":1}\nb: 2\nc:\n- 3\n");
+}
+
+#[test]
+fn format() {
+    let (exit, output) = spawn_nuq(&["--format", "yaml", "."], b"{\"a\": [1]}");
+    assert!(exit.success());
+    assert_eq!(output, "a:\n- 1\n");
+    let (exit, output) = spawn_nuq(&["--format", "yaml", "-o", "json", "."], b"a: 1");
+    assert!(exit.success());
+    assert_eq!(output, "{\"a\":1}\n");
+}
+
+#[test]
+fn preserve_order() {
+    let (exit, output) = spawn_nuq(&["-o", "json", "."], b"b: 1\na:\n  d: 2\n  c: 3\n");
+    assert!(exit.success());
+    assert_eq!(output, "{\"b\":1,\"a\":{\"d\":2,\"c\":3}}\n");
+    let (exit, output) = spawn_nuq(&["-i", "toml", "-o", "yaml", "."], b"z = 1\ny = 2\n");
+    assert!(exit.success());
+    assert_eq!(output, "z: 1\ny: 2\n");
+}
+
+#[test]
+fn sort_keys() {
+    let (exit, output) = spawn_nuq(&["-S", "."], br#"{"b":1,"a":{"d":2,"c":3}}"#);
+    assert!(exit.success());
+    assert_eq!(output, "{\"a\":{\"c\":3,\"d\":2},\"b\":1}\n");
+    let (exit, output) = spawn_nuq(&["--sort-keys", "-o", "yaml", "."], br#"{"b":1,"a":2}"#);
+    assert!(exit.success());
+    assert_eq!(output, "a: 2\nb: 1\n");
+}
+
+#[test]
+fn raw_output_format() {
+    let input = br#"{"a":["b\nc",1]}"#;
+    let (exit, long) = spawn_nuq(&["-o", "raw", ".a[]"], input);
+    assert!(exit.success());
+    let (exit, short) = spawn_nuq(&["-r", ".a[]"], input);
+    assert!(exit.success());
+    assert_eq!(long, "b\nc\n1\n");
+    assert_eq!(long, short);
+    let (exit, output) = spawn_nuq(&["-i", "raw", "."], input);
+    assert!(!exit.success());
+    assert!(output.is_empty());
+}
+
+#[test]
+fn tab() {
+    let (exit, output) = spawn_nuq(&["--pretty", "--tab", "."], br#"{"a":{"b":1}}"#);
+    assert!(exit.success());
+    assert_eq!(output, "{\n\t\"a\": {\n\t\t\"b\": 1\n\t}\n}\n");
+    let (exit, _) = spawn_nuq(&["--tab", "--indent", "2", "."], br#"{"a":{"b":1}}"#);
+    assert!(!exit.success());
+}
+
+#[test]
+fn validate_only() {
+    let (exit, output) = spawn_nuq(&["--validate-only", "."], b"a: [1, 2]");
+    assert!(exit.success());
+    assert_eq!(output, "");
+    let mut handle = Command::new(BINARY_PATH)
+        .args(["--validate-only", "-i", "json", ".a"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to launch nuq process.");
+    handle.stdin.take().unwrap().write_all(b"[1]").unwrap();
+    let output = handle
+        .wait_with_output()
+        .expect("Failed to await nuq process.");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn json_log_format() {
+    let path = std::env::temp_dir().join("nuq-log-format.json");
+    std::fs::write(&path, "{").expect("failed to write file");
+    let output = Command::new(BINARY_PATH)
+        .args(["--log-format", "json", "."])
+        .arg(&path)
+        .output()
+        .expect("Failed to launch nuq process.");
+    std::fs::remove_file(&path).expect("failed to remove file");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).expect("Output is invalid utf8.");
+    let line: serde_json::Value =
+        serde_json::from_str(stderr.trim_end()).expect("error line is invalid json");
+    assert_eq!(line["level"], "error");
+    assert_eq!(line["file"], path.to_string_lossy().as_ref());
+    assert!(line["message"].is_string());
+}
+
+#[test]
+fn raw0() {
+    let (exit, output) = spawn_nuq(&["-r0", "-i", "json", ".[]"], br#"["a b","c"]"#);
+    assert!(exit.success());
+    assert_eq!(output, "a b\0c\0");
+    let (exit, _) = spawn_nuq(&["-0", "."], b"{}");
+    assert!(!exit.success());
+}
+
+#[test]
+fn program_from_env() {
+    let mut handle = Command::new(BINARY_PATH)
+        .args(["-r"])
+        .env("NUQ_PROGRAM", ".key")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to launch nuq process.");
+    handle
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"key: test")
+        .unwrap();
+    let output = handle
+        .wait_with_output()
+        .expect("Failed to await nuq process.");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"test\n");
+}
+
+#[test]
+fn compact() {
+    let input = b"{\n  \"a\": [\n    1,\n    2\n  ]\n}\n";
+    let (exit, output) = spawn_nuq(&["--compact", "-p", "-i", "json", "."], input);
+    assert!(exit.success());
+    assert_eq!(output, "{\"a\":[1,2]}\n");
+}
+
+#[test]
+fn base64() {
+    // {"a":1}
+    let (exit, output) = spawn_nuq(
+        &["--base64-decode", "--base64-encode", "-o", "yaml", "."],
+        b"eyJhIjox\nfQ==\n",
+    );
+    assert!(exit.success());
+    // a: 1\n
+    assert_eq!(output, "YTogMQo=\n");
+}
+
+#[test]
+fn flatten_separator() {
+    let style = [
+        "--flatten-separator",
+        "/",
+        "--flatten-array-style",
+        "bracket",
+    ];
+    let (exit, flat) = spawn_nuq(
+        &[&["-o", "json", "--flatten"], &style[..], &["."]].concat(),
+        br#"{"a.b": [{"c": 1}]}"#,
+    );
+    assert!(exit.success());
+    assert_eq!(flat, "{\"a.b[0]/c\":1}\n");
+    let (exit, nested) = spawn_nuq(
+        &[&["-o", "json", "--unflatten"], &style[..], &["."]].concat(),
+        flat.as_bytes(),
+    );
+    assert!(exit.success());
+    assert_eq!(nested, "{\"a.b\":[{\"c\":1}]}\n");
+}
+
+#[test]
+fn gzip_input() {
+    let path = std::env::temp_dir().join("nuq-gzip-input.yaml.gz");
+    let mut encoder = flate2::write::GzEncoder::new(
+        std::fs::File::create(&path).expect("failed to create file"),
+        flate2::Compression::default(),
+    );
+    encoder
+        .write_all(b"key: compressed")
+        .expect("failed to compress");
+    encoder.finish().expect("failed to finish compression");
+    let (exit, output) = spawn_nuq(&["-r", ".key", &path.to_string_lossy()], b"");
+    std::fs::remove_file(&path).expect("failed to remove file");
+    assert!(exit.success());
+    assert_eq!(output, "compressed\n");
+}
+
+#[test]
+fn null_input() {
+    let (exit, output) = spawn_nuq(&["-n", "1+1"], b"");
+    assert!(exit.success());
+    assert_eq!(output, "2\n");
+    let (exit, output) = spawn_nuq(&["-n", "{a: .}"], b"ignored: input");
+    assert!(exit.success());
+    assert_eq!(output, "{\"a\":null}\n");
+}
+
+#[test]
+fn shuffle() {
+    let args = ["-r", "--shuffle", "--seed", "42", ".[]"];
+    let (exit, output) = spawn_nuq(&args, b"[1, 2, 3, 4, 5]");
+    assert!(exit.success());
+    assert_eq!(output, "2\n3\n1\n5\n4\n");
+    std::fs::write("./mock-shuffle1.json", "1 2 3").expect("failed to create mock json");
+    std::fs::write("./mock-shuffle2.json", "4 5").expect("failed to create mock json");
+    let args = [
+        "-r",
+        "--shuffle",
+        "--seed",
+        "3",
+        ".",
+        "mock-shuffle1.json",
+        "mock-shuffle2.json",
+    ];
+    let (exit, output) = spawn_nuq(&args, b"");
+    std::fs::remove_file("./mock-shuffle1.json").expect("failed to remove mock json");
+    std::fs::remove_file("./mock-shuffle2.json").expect("failed to remove mock json");
+    // documents move between the inputs
+    assert!(exit.success());
+    assert_eq!(output, "3\n5\n1\n2\n4\n");
+}
+
+#[test]
+fn raw_input() {
+    let (exit, output) = spawn_nuq(&["-R", "-o", "json", "."], b"a: 1\nb\n");
+    assert!(exit.success());
+    assert_eq!(output, "\"a: 1\"\n\"b\"\n");
+    let (exit, output) = spawn_nuq(&["-R", "--slurp", "-o", "json", "."], b"a: 1\nb\n");
+    assert!(exit.success());
+    assert_eq!(output, "[\"a: 1\",\"b\"]\n");
+}
+
+#[test]
+fn required_keys() {
+    let args = ["--required-keys", "a,b.c", "-o", "json", ".a"];
+    let (exit, output) = spawn_nuq(&args, br#"{"a": 1, "b": {"c": null}}"#);
+    assert!(exit.success());
+    assert_eq!(output, "1\n");
+    let (exit, _) = spawn_nuq(&args, br#"{"a": 1, "b": {}}"#);
+    assert!(!exit.success());
+    let (exit, output) = spawn_nuq(
+        &[&["--collect-errors"], &args[..]].concat(),
+        br#"{"a": 1, "b": {}}"#,
+    );
+    assert!(exit.success());
+    assert_eq!(output, "1\n");
+}
+
+#[test]
+fn dry_run() {
+    let path = std::env::temp_dir().join("nuq-dry-run.yaml");
+    std::fs::write(&path, "a: 1\n").expect("failed to create file");
+    let file = path.to_string_lossy();
+    let (exit, changed) = spawn_nuq(&["--dry-run", "-O", &file, ".a = 2"], b"a: 1");
+    let content = std::fs::read_to_string(&path).expect("failed to read file");
+    let (_, unchanged) = spawn_nuq(&["--dry-run", "-O", &file, "."], b"a: 1");
+    std::fs::remove_file(&path).expect("failed to remove file");
+    assert!(exit.success());
+    assert_eq!(changed, format!("{file}: would change\n"));
+    assert_eq!(content, "a: 1\n");
+    assert_eq!(unchanged, format!("{file}: unchanged\n"));
+}
+
+#[test]
+fn slurpfile() {
+    let path = std::env::temp_dir().join("nuq-slurpfile.yaml");
+    std::fs::write(&path, "a: 1\n---\na: 2\n").expect("failed to create file");
+    let file = path.to_string_lossy();
+    let (exit, output) = spawn_nuq(&["--slurpfile", "docs", &file, "-n", "$docs | length"], b"");
+    let (_, sum) = spawn_nuq(
+        &["--slurpfile", "docs", &file, "-n", "[$docs[].a] | add"],
+        b"",
+    );
+    std::fs::remove_file(&path).expect("failed to remove file");
+    assert!(exit.success());
+    assert_eq!(output, "2\n");
+    assert_eq!(sum, "3\n");
+}
+
+#[test]
+fn omitted_program() {
+    let path = std::env::temp_dir().join("nuq-omitted-program.yaml");
+    std::fs::write(&path, "key: test\n").expect("failed to create file");
+    let file = path.to_string_lossy();
+    let (exit, converted) = spawn_nuq(&["-o", "json", &file], b"");
+    let (_, filtered) = spawn_nuq(&["-r", ".key", &file], b"");
+    std::fs::remove_file(&path).expect("failed to remove file");
+    assert!(exit.success());
+    assert_eq!(converted, "{\"key\":\"test\"}\n");
+    assert_eq!(filtered, "test\n");
+    let (exit, stdin) = spawn_nuq(&["-i", "yaml", "-o", "json"], b"key: test");
+    assert!(exit.success());
+    assert_eq!(stdin, "{\"key\":\"test\"}\n");
+}
+
+#[test]
+fn program_named_like_file() {
+    std::fs::write("./keys", "b: 1\n").expect("failed to create file");
+    let (exit, program) = spawn_nuq(&["keys"], br#"{"a":1}"#);
+    let (_, file) = spawn_nuq(&["-i", "yaml", "-o", "json", "keys"], b"");
+    std::fs::remove_file("./keys").expect("failed to remove file");
+    assert!(exit.success());
+    assert_eq!(program, "[\"a\"]\n");
+    assert_eq!(file, "{\"b\":1}\n");
+}
+
+#[test]
+fn quiet() {
+    let (exit, output) = spawn_nuq(&["-q", "."], br#"{"a":1}"#);
+    assert!(exit.success());
+    assert_eq!(output, "");
+}
+
+#[test]
+fn output_file() {
+    let path = std::env::temp_dir().join("nuq-output-file.toml");
+    let path_arg = path.to_string_lossy();
+    let (exit, output) = spawn_nuq(&["-O", &path_arg, "."], b"a: 1\nb: [x]");
+    let written = std::fs::read_to_string(&path).expect("failed to read output file");
+    std::fs::remove_file(&path).expect("failed to remove output file");
+    assert!(exit.success());
+    assert_eq!(output, "");
+    assert_eq!(written, "a = 1\nb = [\"x\"]\n");
+}
+
+#[test]
+fn only() {
+    let dir = std::env::temp_dir().join("nuq-only");
+    std::fs::create_dir_all(&dir).expect("failed to create directory");
+    let files = [
+        ("a.yaml", "a: 1"),
+        ("b.json", r#"{"b":2}"#),
+        ("c.txt", "c"),
+        ("d.yml", "d: 4"),
+    ];
+    let mut args = vec!["--only".to_owned(), "yaml".to_owned(), ".".to_owned()];
+    for (name, content) in files {
+        let path = dir.join(name);
+        std::fs::write(&path, content).expect("failed to write file");
+        args.push(path.to_string_lossy().into_owned());
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (exit, output) = spawn_nuq(&args, b"");
+    std::fs::remove_dir_all(&dir).expect("failed to remove directory");
+    assert!(exit.success());
+    assert_eq!(output, "a: 1\nd: 4\n");
 }
 
 fn spawn_nuq(args: &[&str], input: &[u8]) -> (ExitStatus, String) {